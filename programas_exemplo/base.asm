@@ -0,0 +1,13 @@
+. Programa teste das diretivas BASE e NOBASE
+T_BASE  START 0
+
+INICIO      +LDB #TABELA
+            BASE TABELA
+            LDA TABELA
+            LDA FIM
+            NOBASE
+BUFFER      RESB 4096
+TABELA      WORD 7
+FIM         WORD 8
+
+            END INICIO
@@ -0,0 +1,17 @@
+. Programa teste dos blocos de programa
+T_USE   START 0
+
+INICIO      LDA TAMANHO
+            USE CDADOS
+TAMANHO     WORD 5
+            USE CBLKS
+BUFFER      RESB 4096
+BUFEND      EQU *
+MAXLEN      EQU BUFEND-BUFFER
+            USE
+            +LDT #MAXLEN
+            J INICIO
+            USE CDADOS
+FIM         WORD 0
+
+            END INICIO
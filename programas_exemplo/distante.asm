@@ -0,0 +1,8 @@
+. Programa teste de símbolo fora do alcance do formato 3
+T_DIST  START 1000
+
+INICIO      LDA DADO
+BUFFER      RESB 4096
+DADO        WORD 5
+
+            END INICIO
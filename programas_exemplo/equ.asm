@@ -0,0 +1,14 @@
+. Programa teste da diretiva EQU e de expressões
+T_EQU   START 1000
+
+MAXLEN      EQU 4096
+INICIO      +LDT #MAXLEN
+            LDA #TAMANHO
+            LDX TABELA+3
+FIM         WORD BUFEND-BUFFER+1
+BUFFER      RESB 10
+BUFEND      EQU *
+TAMANHO     EQU BUFEND-BUFFER
+TABELA      RESW TAMANHO/5
+
+            END INICIO
@@ -0,0 +1,8 @@
+. Programa teste dos diagnósticos do montador
+T_ERRO  START 0
+INICIO      LDA VALOR
+            LDZ VALOR
+INICIO      STA VALOR
+SOBRA
+VALOR       WORD 3
+            END INICIO
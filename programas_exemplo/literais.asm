@@ -0,0 +1,10 @@
+. Programa teste do pool de literais
+T_LIT   START 0
+
+INICIO      LDA =X'000005'
+            COMP =C'EOF'
+            LTORG
+            ADD =3
+            ADD =3 . Literal repetido usa o mesmo endereço
+
+            END INICIO
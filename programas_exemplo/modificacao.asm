@@ -0,0 +1,11 @@
+. Programa teste dos registros de modificação
+T_MOD   START 0
+
+INICIO      +JSUB ROTINA
+            J FIM
+ROTINA      RSUB
+PONTEIRO    WORD ROTINA
+TAMANHO     WORD FIM-INICIO . Absoluto, não precisa de modificação
+FIM         +LDA #5
+
+            END INICIO
@@ -0,0 +1,10 @@
+. Programa teste da diretiva ORG
+T_ORG   START 0
+
+INICIO      LDA VALOR
+            ORG INICIO+16
+VALOR       WORD 3
+            ORG . Volta para depois do LDA
+            J INICIO
+
+            END INICIO
@@ -0,0 +1,11 @@
+. Programa teste do endereçamento relativo ao PC
+T_REL   START 1000
+
+INICIO      LDA CINCO
+            ADD CINCO
+            STA RESULT
+            J INICIO . Deslocamento negativo
+CINCO       WORD 5
+RESULT      RESW 1
+
+            END INICIO
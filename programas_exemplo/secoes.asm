@@ -0,0 +1,18 @@
+. Programa teste das seções de controle
+PROGA   START 0
+            EXTDEF LISTA,ENDA
+            EXTREF LISTB,ROTB
+INICIO      +JSUB ROTB
+            LDA LISTA
+LISTA       WORD 4
+ENDA        EQU *
+REF1        WORD LISTB-4
+
+PROGB   CSECT
+            EXTDEF LISTB,ROTB
+            EXTREF LISTA,ENDA
+ROTB        +LDA LISTA
+            RSUB
+LISTB       WORD ENDA-LISTA
+
+            END INICIO
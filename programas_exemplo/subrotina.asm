@@ -0,0 +1,16 @@
+. Programa teste de sub-rotinas e desvios
+T_SUB   START 0
+
+INICIO      LDX #0
+LACO        JSUB DOBRAR
+            TIX #3
+            JLT LACO . Deslocamento negativo
+            STA RESULT
+            J @FIMPTR
+DOBRAR      ADD #2
+            RSUB
+FIMPTR      WORD FIM
+FIM         J FIM
+RESULT      RESW 1
+
+            END INICIO
@@ -1,4 +1,6 @@
+use crate::maquina::desmontador::{self, LinhaDesmontada};
 use crate::maquina::maquina::Maquina;
+use crate::montador::diagnostico::Diagnostico;
 use crate::montador::montador;
 use crate::processador_macros::macros;
 use anyhow::Context;
@@ -6,7 +8,8 @@ use rfd::FileDialog;
 use std::fs::File;
 use std::io::Write;
 
-pub fn carregar_programa(maquina: &mut Maquina) -> anyhow::Result<()> {
+/// Monta e carrega um programa, retornando os avisos da montagem.
+pub fn carregar_programa(maquina: &mut Maquina) -> anyhow::Result<Vec<Diagnostico>> {
     // 1. Abre a janela para selecionar o arquivo .asm
     let arquivo = FileDialog::new()
         .set_title("Carregar código fonte (.asm)")
@@ -24,44 +27,68 @@ pub fn carregar_programa(maquina: &mut Maquina) -> anyhow::Result<()> {
     let conteudo_asm =
         std::fs::read_to_string("MASMAPRG.ASM").context("Erro ao ler MASMAPRG.ASM")?;
 
-    // 4. Roda o Montador (Etapa 2)
-    let tabela_simbolos = montador::primeiro_passo(&conteudo_asm)?;
-    let registro_objeto = montador::segundo_passo(&conteudo_asm, &tabela_simbolos)?;
+    // 4. Roda o Montador (Etapa 2), o erro contém todos os diagnósticos
+    let montagem = montador::montar(&conteudo_asm)?;
 
-    // 5. Converte a string do registro objeto (formato H T E) em bytes reais
-    // Vamos focar no registro 'T' (Text) que contém o código
-    let bytes = extrair_bytes_do_objeto(&registro_objeto)?;
+    // 5. Salva o programa objeto e a listagem ao lado do código fonte
+    std::fs::write(arquivo.with_extension("obj"), &montagem.objeto)
+        .context("Erro ao salvar o programa objeto")?;
+    std::fs::write(arquivo.with_extension("lst"), &montagem.listagem)
+        .context("Erro ao salvar a listagem")?;
 
-    // 6. Finalmente carrega na memória da máquina
-    maquina.carregar(&bytes)
+    // 6. Carrega o programa objeto na memória da máquina
+    maquina.carregar_objeto(&montagem.objeto)?;
+    maquina.adicionar_simbolos(&montagem.simbolos);
+    Ok(montagem.avisos)
 }
 
-/// Função auxiliar para transformar a string "HTE..." em bytes de verdade
-fn extrair_bytes_do_objeto(objeto: &str) -> anyhow::Result<Vec<u8>> {
-    let mut bytes = Vec::new();
-
-    // MUDANÇA: Usamos um loop 'for' para ler TODAS as linhas T (Card #49)
-    for linha in objeto.lines() {
-        if linha.starts_with('T') {
-            // No formato SIC/XE: T (1 char) + Endereço (6 chars) + Tamanho (2 chars) = 9 chars de cabeçalho
-            // O código real começa no índice 9
-            if linha.len() > 9 {
-                let codigo_hex = &linha[9..];
+/// Liga vários programas objeto (.obj) já montados, como uma biblioteca de rotinas
+/// e o programa que as usa, carregando-os a partir do endereço de carga da máquina.
+pub fn ligar_programas(maquina: &mut Maquina) -> anyhow::Result<()> {
+    let arquivos = FileDialog::new()
+        .set_title("Ligar programas objeto (.obj)")
+        .add_filter("Programa objeto SIC/XE (.obj)", &["obj"])
+        .pick_files()
+        .context("Nenhum arquivo selecionado")?;
 
-                let mut chars = codigo_hex.chars();
-                while let (Some(d1), Some(d2)) = (chars.next(), chars.next()) {
-                    let par = format!("{}{}", d1, d2);
-                    if let Ok(byte) = u8::from_str_radix(&par, 16) {
-                        bytes.push(byte);
-                    }
-                }
-            }
-        }
+    let mut objetos = Vec::with_capacity(arquivos.len());
+    for arquivo in &arquivos {
+        objetos.push(
+            std::fs::read_to_string(arquivo)
+                .with_context(|| format!("Erro ao ler {}", arquivo.display()))?,
+        );
     }
 
-    if bytes.is_empty() {
-        return Err(anyhow::anyhow!("Nenhum registro de texto (T) encontrado"));
-    }
+    let objetos: Vec<&str> = objetos.iter().map(String::as_str).collect();
+    maquina.ligar_objetos(&objetos, maquina.endereco_carga_padrao())
+}
+
+/// Desmonta um programa objeto (.obj) ou um arquivo com os bytes em hexadecimal (.hex),
+/// retornando o nome do arquivo e as linhas desmontadas.
+pub fn desmontar_arquivo() -> anyhow::Result<(String, Vec<LinhaDesmontada>)> {
+    let arquivo = FileDialog::new()
+        .set_title("Desmontar programa (.obj ou .hex)")
+        .add_filter("Programa SIC/XE (.obj, .hex)", &["obj", "hex"])
+        .pick_file()
+        .context("Nenhum arquivo selecionado")?;
+
+    let conteudo = std::fs::read_to_string(&arquivo)
+        .with_context(|| format!("Erro ao ler {}", arquivo.display()))?;
+
+    // Os arquivos .hex não têm endereço, os bytes são desmontados a partir do 0
+    let linhas = if arquivo
+        .extension()
+        .is_some_and(|extensao| extensao == "hex")
+    {
+        desmontador::desmontar_hex(&conteudo, 0)?
+    } else {
+        desmontador::desmontar_objeto(&conteudo)?
+    };
+
+    let nome = arquivo
+        .file_name()
+        .map(|nome| nome.to_string_lossy().to_string())
+        .unwrap_or_default();
 
-    Ok(bytes)
+    Ok((nome, linhas))
 }
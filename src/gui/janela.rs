@@ -1,37 +1,120 @@
-use crate::gui::carregar_programa::carregar_programa;
-use crate::maquina::maquina::Maquina;
+use crate::gui::carregar_programa::{carregar_programa, desmontar_arquivo, ligar_programas};
+use crate::maquina::constantes::registradores;
+use crate::maquina::desmontador::{self, LinhaDesmontada};
+use crate::maquina::dispositivos::Fila;
+use crate::maquina::instrucao;
+use crate::maquina::maquina::{
+    ENDERECO_CARGA, Maquina, MotivoParada, TAMANHO_MEMORIA_MAXIMO, TAMANHO_MEMORIA_SIC,
+};
 use eframe::egui;
 
+/// Instruções executadas a cada quadro enquanto o programa está em execução.
+const PASSOS_POR_QUADRO: usize = 1000;
+
 pub struct Janela {
     maquina: Maquina,
     erro: Option<String>,
+    /// Avisos da última montagem
+    avisos: Option<String>,
     status: String,
     executando: bool,
+    /// Dispositivo ligado ao console, um clone dele fica conectado na máquina
+    console: Fila,
+    numero_console: u8,
+    /// Número digitado para o console, aplicado só ao confirmar
+    novo_numero_console: u8,
+    /// Texto digitado que ainda não foi enviado ao console
+    entrada_console: String,
+    /// Tudo que a máquina já escreveu no console
+    saida_console: String,
+    /// Configuração usada ao recriar a máquina
+    tamanho_memoria: usize,
+    endereco_carga: usize,
+    /// Mostra o programa carregado desmontado no lugar dos bytes
+    ver_assembly: bool,
+    /// Nome e linhas do último arquivo desmontado, mostrado numa janela própria
+    arquivo_desmontado: Option<(String, Vec<LinhaDesmontada>)>,
+    /// Símbolo ou endereço digitado para um novo ponto de parada
+    novo_ponto_parada: String,
 }
 
 impl Default for Janela {
     fn default() -> Self {
+        let console = Fila::new();
+        let mut maquina = Maquina::new();
+        conectar_console(&mut maquina, &console, 0x00);
+
         Self {
-            maquina: Maquina::new(),
+            maquina,
             erro: None,
+            avisos: None,
             status: "✅ Sistema pronto.".to_string(),
             executando: false,
+            console,
+            numero_console: 0x00,
+            novo_numero_console: 0x00,
+            entrada_console: String::new(),
+            saida_console: String::new(),
+            tamanho_memoria: TAMANHO_MEMORIA_SIC,
+            endereco_carga: ENDERECO_CARGA,
+            ver_assembly: false,
+            arquivo_desmontado: None,
+            novo_ponto_parada: String::new(),
         }
     }
 }
 
+impl Janela {
+    /// Recria a máquina com a configuração atual, removendo o programa carregado.
+    fn recriar_maquina(&mut self) -> anyhow::Result<()> {
+        let mut maquina = Maquina::construtor()
+            .tamanho_memoria(self.tamanho_memoria)
+            .endereco_carga(self.endereco_carga)
+            .construir()?;
+
+        conectar_console(&mut maquina, &self.console, self.numero_console);
+        self.maquina = maquina;
+        self.executando = false;
+        Ok(())
+    }
+}
+
+/// Conecta o console no dispositivo informado. Os dispositivos 00 e 01 da máquina são
+/// o terminal, e um RD neles travaria a interface esperando a entrada padrão, então
+/// eles usam os arquivos deles quando não são o console.
+fn conectar_console(maquina: &mut Maquina, console: &Fila, numero: u8) {
+    maquina.desconectar_dispositivo(0x00);
+    maquina.desconectar_dispositivo(0x01);
+    maquina.conectar_dispositivo(numero, Box::new(console.clone()));
+}
+
 impl eframe::App for Janela {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         if self.executando {
-            if let Err(error) = self.maquina.executar_instrucao() {
-                self.erro = Some(error.to_string());
-                self.executando = false;
-            } else {
+            match self.maquina.executar_ate_parar(PASSOS_POR_QUADRO) {
                 // Rodar esta função update de novo
-                ctx.request_repaint();
+                MotivoParada::LimitePassos => ctx.request_repaint(),
+                MotivoParada::PontoParada(endereco) => {
+                    self.executando = false;
+                    self.status = format!("🛑 Parado no ponto de parada {:06X}.", endereco);
+                }
+
+                MotivoParada::Fim => {
+                    self.executando = false;
+                    self.status = "Execução finalizada.".to_string();
+                }
+
+                MotivoParada::Erro(error) => {
+                    self.erro = Some(error.to_string());
+                    self.executando = false;
+                }
             }
         }
 
+        let saida = self.console.retirar_saida();
+        self.saida_console
+            .push_str(&String::from_utf8_lossy(&saida));
+
         // TOPO (Menu de controle)
         egui::TopBottomPanel::top("barra_superior").show(ctx, |ui| {
             ui.horizontal_centered(|ui| {
@@ -39,11 +122,34 @@ impl eframe::App for Janela {
                 ui.separator();
 
                 if ui.button("📂 Carregar programa").clicked() {
-                    if let Err(error) = carregar_programa(&mut self.maquina) {
+                    self.avisos = None;
+                    match carregar_programa(&mut self.maquina) {
+                        Err(error) => self.erro = Some(error.to_string()),
+                        Ok(avisos) => {
+                            self.erro = None;
+                            self.status = "Programa carregado com sucesso.".to_string();
+                            if !avisos.is_empty() {
+                                let avisos: Vec<String> =
+                                    avisos.iter().map(ToString::to_string).collect();
+                                self.avisos = Some(avisos.join("\n\n"));
+                            }
+                        }
+                    }
+                }
+
+                if ui.button("🔗 Ligar objetos").clicked() {
+                    if let Err(error) = ligar_programas(&mut self.maquina) {
                         self.erro = Some(error.to_string());
                     } else {
                         self.erro = None;
-                        self.status = "Programa carregado com sucesso.".to_string();
+                        self.status = "Programas ligados com sucesso.".to_string();
+                    }
+                }
+
+                if ui.button("🔍 Desmontar arquivo").clicked() {
+                    match desmontar_arquivo() {
+                        Err(error) => self.erro = Some(error.to_string()),
+                        Ok(desmontado) => self.arquivo_desmontado = Some(desmontado),
                     }
                 }
 
@@ -69,6 +175,11 @@ impl eframe::App for Janela {
                     self.executando = false;
                     self.erro = Some("Programa parado.".to_string());
                 }
+
+                let mut interrupcoes = self.maquina.interrupcoes_habilitadas();
+                if ui.checkbox(&mut interrupcoes, "Interrupções").changed() {
+                    self.maquina.habilitar_interrupcoes(interrupcoes);
+                }
             });
         });
 
@@ -81,22 +192,235 @@ impl eframe::App for Janela {
                 ui.separator();
 
                 egui::Grid::new("grid_regs").striped(true).show(ui, |ui| {
-                    let nomes = ["A", "X", "L", "B", "S", "T", "F", "R7", "PC", "SW"];
-                    for (i, nome) in nomes.iter().enumerate() {
-                        if i != 7 {
-                            ui.label(*nome);
-                            ui.label(format!("{:06X}", self.maquina.registrador(i).unwrap_or(0)));
-                            ui.end_row();
+                    for (i, nome) in registradores::NOMES.iter().enumerate() {
+                        // O F tem 48 bits, os outros registradores 24
+                        let valor = self.maquina.registrador(i).unwrap_or(0);
+                        ui.label(*nome);
+                        if i == registradores::F {
+                            ui.label(format!("{:012X}", valor));
+                        } else {
+                            ui.label(format!("{:06X}", valor));
+                        }
+
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.label(if self.maquina.modo_supervisor() {
+                    "Modo supervisor"
+                } else {
+                    "Modo usuário"
+                });
+
+                ui.separator();
+                ui.heading("🔎 Próxima instrução");
+                let pc = self.maquina.registrador(registradores::PC).unwrap_or(0);
+                match instrucao::decodificar(self.maquina.memoria(), pc) {
+                    Ok(instrucao) => {
+                        let flags = instrucao.flags;
+                        let bits = [flags.n, flags.i, flags.x, flags.b, flags.p, flags.e]
+                            .map(|flag| if flag { '1' } else { '0' });
+
+                        // O tamanho é igual ao número do formato
+                        ui.monospace(format!(
+                            "{}  formato {}  opcode {:02X}",
+                            instrucao.definicao.mnemonico,
+                            instrucao.tamanho(),
+                            instrucao.opcode
+                        ));
+                        ui.monospace(format!("nixbpe {}", String::from_iter(bits)));
+                        if let Some(alvo) = instrucao.endereco_alvo(self.maquina.registradores()) {
+                            ui.monospace(format!("Alvo   {:06X}", alvo));
+                        }
+                    }
+
+                    Err(error) => {
+                        ui.label(error.to_string());
+                    }
+                }
+
+                ui.separator();
+                ui.heading("🛑 Pontos de parada");
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.novo_ponto_parada)
+                            .hint_text("Símbolo ou endereço")
+                            .desired_width(120.0),
+                    );
+
+                    // Símbolos têm prioridade sobre endereços, como o label A1
+                    if ui.button("Adicionar").clicked() {
+                        let texto = self.novo_ponto_parada.trim().to_uppercase();
+                        if self.maquina.adicionar_ponto_parada_simbolo(&texto).is_ok() {
+                            self.novo_ponto_parada.clear();
+                        } else if let Ok(endereco) = usize::from_str_radix(&texto, 16) {
+                            self.maquina.adicionar_ponto_parada(endereco);
+                            self.novo_ponto_parada.clear();
+                        } else {
+                            self.erro = Some(format!("Símbolo ou endereço inválido: {}", texto));
+                        }
+                    }
+                });
+
+                let pontos_parada: Vec<usize> =
+                    self.maquina.pontos_parada().iter().copied().collect();
+                for endereco in pontos_parada {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("✖").clicked() {
+                            self.maquina.remover_ponto_parada(endereco);
+                        }
+
+                        ui.monospace(format!("{:06X}", endereco));
+                    });
+                }
+
+                ui.separator();
+                ui.heading("⚙️ Configuração");
+                egui::ComboBox::from_label("Memória")
+                    .selected_text(format!("{} KB", self.tamanho_memoria / 1024))
+                    .show_ui(ui, |ui| {
+                        let mut tamanho = TAMANHO_MEMORIA_SIC;
+                        while tamanho <= TAMANHO_MEMORIA_MAXIMO {
+                            ui.selectable_value(
+                                &mut self.tamanho_memoria,
+                                tamanho,
+                                format!("{} KB", tamanho / 1024),
+                            );
+                            tamanho *= 2;
                         }
+                    });
+
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::DragValue::new(&mut self.endereco_carga)
+                            .hexadecimal(6, false, true)
+                            .range(0..=TAMANHO_MEMORIA_MAXIMO - 1),
+                    );
+                    ui.label("Endereço de carga");
+                });
+
+                if ui.button("Aplicar").clicked() {
+                    match self.recriar_maquina() {
+                        Err(error) => self.erro = Some(error.to_string()),
+                        Ok(()) => {
+                            self.erro = None;
+                            self.status =
+                                "Máquina recriada, carregue o programa de novo.".to_string();
+                        }
+                    }
+                }
+            });
+
+        // PAINEL DIREITO (Console), um RD no dispositivo do console espera até o usuário
+        // enviar algum texto
+        egui::SidePanel::right("painel_console")
+            .resizable(true)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.heading("🖥️ Console");
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    ui.label("Dispositivo");
+                    let numero = ui.add(
+                        egui::DragValue::new(&mut self.novo_numero_console)
+                            .hexadecimal(2, false, true),
+                    );
+
+                    // O dispositivo anterior volta a usar o arquivo dele
+                    let alterado = self.novo_numero_console != self.numero_console;
+                    let aplicar = ui.add_enabled(alterado, egui::Button::new("Aplicar"));
+                    if alterado && (aplicar.clicked() || numero.lost_focus()) {
+                        self.maquina.desconectar_dispositivo(self.numero_console);
+                        self.numero_console = self.novo_numero_console;
+                        conectar_console(&mut self.maquina, &self.console, self.numero_console);
+                    }
+
+                    if ui.button("🧹 Limpar").clicked() {
+                        self.saida_console.clear();
                     }
                 });
+
+                // Entrada no rodapé do painel, a saída ocupa o resto
+                egui::TopBottomPanel::bottom("entrada_console").show_inside(ui, |ui| {
+                    ui.horizontal(|ui| {
+                        let campo = ui.text_edit_singleline(&mut self.entrada_console);
+                        let enter =
+                            campo.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+
+                        if ui.button("Enviar").clicked() || enter {
+                            self.entrada_console.push('\n');
+                            self.console
+                                .adicionar_entrada(self.entrada_console.as_bytes());
+                            self.entrada_console.clear();
+                            campo.request_focus();
+                        }
+                    });
+                });
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .stick_to_bottom(true)
+                    .show(ui, |ui| {
+                        ui.monospace(&self.saida_console);
+                    });
             });
 
         // PAINEL CENTRAL (Memória + Código)
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("💾 Memória e Código");
+            ui.horizontal(|ui| {
+                ui.heading("💾 Memória e Código");
+                ui.separator();
+                ui.selectable_value(&mut self.ver_assembly, false, "Hexadecimal");
+                ui.selectable_value(&mut self.ver_assembly, true, "Assembly");
+            });
             ui.separator();
 
+            if self.ver_assembly {
+                let inicio = self.maquina.endereco_carga() as u64;
+                let fim = inicio + self.maquina.tamanho_programa() as u64;
+                let pc = self.maquina.registrador(registradores::PC).unwrap_or(0);
+                let base = self.maquina.registrador(registradores::B);
+                let linhas = desmontador::desmontar(
+                    self.maquina.memoria(),
+                    inicio,
+                    fim,
+                    base,
+                    self.maquina.simbolos(),
+                );
+
+                egui::ScrollArea::vertical()
+                    .auto_shrink(false)
+                    .show(ui, |ui| {
+                        // A próxima instrução fica destacada e clicar numa linha alterna
+                        // o ponto de parada dela
+                        for linha in linhas {
+                            let endereco = linha.endereco as usize;
+                            let parada = self.maquina.pontos_parada().contains(&endereco);
+                            let marcador = if parada { "●" } else { " " };
+                            let mut texto =
+                                egui::RichText::new(format!("{} {}", marcador, linha)).monospace();
+
+                            if linha.endereco == pc {
+                                texto = texto.color(egui::Color32::LIGHT_GREEN);
+                            } else if parada {
+                                texto = texto.color(egui::Color32::LIGHT_RED);
+                            }
+
+                            let resposta = ui
+                                .add(egui::Label::new(texto).sense(egui::Sense::click()))
+                                .on_hover_text("Clique para alternar o ponto de parada");
+
+                            if resposta.clicked() {
+                                self.maquina.alternar_ponto_parada(endereco);
+                            }
+                        }
+                    });
+
+                return;
+            }
+
             let memoria = self.maquina.memoria();
             egui::ScrollArea::vertical()
                 .auto_shrink(false)
@@ -119,7 +443,10 @@ impl eframe::App for Janela {
                         }
 
                         ui.separator();
-                        for addr in (0x6000..0x6060).step_by(8) {
+                        // Início do programa atual, alinhado em 8 bytes
+                        let inicio = self.maquina.endereco_carga() & !7;
+                        let fim = (inicio + 0x60).min(memoria.len());
+                        for addr in (inicio..fim).step_by(8) {
                             let slice = &memoria[addr..addr + 8];
                             ui.monospace(format!(
                                 "{:04X}: {:02X} {:02X} {:02X} {:02X}  {:02X} {:02X} {:02X} {:02X}",
@@ -138,18 +465,51 @@ impl eframe::App for Janela {
                 });
         });
 
-        // RODAPÉ (Mensagens)
+        if let Some((nome, linhas)) = &self.arquivo_desmontado {
+            let mut aberta = true;
+            egui::Window::new(format!("🔍 {}", nome))
+                .open(&mut aberta)
+                .default_size(egui::vec2(520.0, 400.0))
+                .show(ctx, |ui| {
+                    egui::ScrollArea::vertical()
+                        .auto_shrink(false)
+                        .show(ui, |ui| {
+                            for linha in linhas {
+                                ui.monospace(linha.to_string());
+                            }
+                        });
+                });
+
+            if !aberta {
+                self.arquivo_desmontado = None;
+            }
+        }
+
+        // RODAPÉ (Mensagens), os diagnósticos do montador podem ocupar várias linhas
         egui::TopBottomPanel::bottom("painel_erros")
-            .resizable(false)
+            .resizable(true)
             .default_height(35.0)
             .show(ctx, |ui| {
-                ui.horizontal_centered(|ui| {
-                    if let Some(erro) = &self.erro {
-                        ui.colored_label(egui::Color32::LIGHT_RED, erro);
-                    } else {
-                        ui.colored_label(egui::Color32::LIGHT_GREEN, &self.status);
-                    }
-                });
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, true])
+                    .max_height(200.0)
+                    .show(ui, |ui| {
+                        if let Some(erro) = &self.erro {
+                            ui.colored_label(
+                                egui::Color32::LIGHT_RED,
+                                egui::RichText::new(erro).monospace(),
+                            );
+                        } else {
+                            ui.colored_label(egui::Color32::LIGHT_GREEN, &self.status);
+                        }
+
+                        if let Some(avisos) = &self.avisos {
+                            ui.colored_label(
+                                egui::Color32::YELLOW,
+                                egui::RichText::new(avisos).monospace(),
+                            );
+                        }
+                    });
             });
     }
 }
@@ -0,0 +1,294 @@
+use anyhow::{Context, anyhow};
+use std::collections::HashMap;
+
+/// Programa objeto lido dos registros H, D, R, T, M e E gerados pelo montador.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProgramaObjeto {
+    pub nome: String,
+    pub endereco_inicial: usize,
+    pub tamanho: usize,
+    /// Símbolos exportados pelo registro D e seus endereços
+    pub definicoes: Vec<(String, usize)>,
+    /// Símbolos importados pelo registro R
+    pub referencias: Vec<String>,
+    /// Endereço e bytes de cada registro T
+    pub textos: Vec<(usize, Vec<u8>)>,
+    pub modificacoes: Vec<Modificacao>,
+    /// Endereço do registro E, existe somente na seção principal
+    pub endereco_execucao: Option<usize>,
+}
+
+/// Registro M, que soma ou subtrai o endereço de um símbolo de um campo da memória.
+/// Sem símbolo, o endereço somado é o do próprio programa.
+#[derive(Debug, PartialEq)]
+pub struct Modificacao {
+    pub endereco: usize,
+    /// Tamanho do campo em meio bytes
+    pub tamanho: usize,
+    pub simbolo: Option<String>,
+    pub negativa: bool,
+}
+
+/// Lê todos os programas objeto de um texto, cada um terminado pelo seu registro E.
+pub fn ler_programas(objeto: &str) -> anyhow::Result<Vec<ProgramaObjeto>> {
+    let mut programas = Vec::new();
+    let mut atual: Option<ProgramaObjeto> = None;
+
+    for linha in objeto.lines().map(str::trim_end) {
+        let Some(tipo) = linha.chars().next() else {
+            continue;
+        };
+
+        if tipo == 'H' {
+            if atual.is_some() {
+                return Err(anyhow!(
+                    "Registro H antes do registro E do programa anterior"
+                ));
+            }
+
+            // Os 12 últimos caracteres são o endereço inicial e o tamanho
+            let inicio_numeros = linha
+                .len()
+                .checked_sub(12)
+                .filter(|inicio| *inicio >= 1)
+                .context("Registro H inválido")?;
+
+            let nome = linha
+                .get(1..inicio_numeros)
+                .context("Registro H inválido")?;
+
+            atual = Some(ProgramaObjeto {
+                nome: nome.trim().to_string(),
+                endereco_inicial: ler_hex(linha, inicio_numeros..inicio_numeros + 6)?,
+                tamanho: ler_hex(linha, inicio_numeros + 6..inicio_numeros + 12)?,
+                ..Default::default()
+            });
+
+            continue;
+        }
+
+        let Some(programa) = atual.as_mut() else {
+            return Err(anyhow!("Registro {} fora de um programa", tipo));
+        };
+
+        match tipo {
+            'T' => {
+                let endereco = ler_hex(linha, 1..7)?;
+                let tamanho = ler_hex(linha, 7..9)?;
+
+                let mut bytes = Vec::with_capacity(tamanho);
+                for i in 0..tamanho {
+                    bytes.push(ler_hex(linha, 9 + 2 * i..11 + 2 * i)? as u8);
+                }
+
+                programa.textos.push((endereco, bytes));
+            }
+
+            // O campo modificado tem no máximo 6 meio bytes, como o endereço do formato 4
+            'M' => {
+                let tamanho = ler_hex(linha, 7..9)?;
+                if !(1..=6).contains(&tamanho) {
+                    return Err(anyhow!(
+                        "Tamanho inválido no registro de modificação: {}",
+                        linha
+                    ));
+                }
+
+                let simbolo = linha.get(10..).filter(|simbolo| !simbolo.is_empty());
+                programa.modificacoes.push(Modificacao {
+                    endereco: ler_hex(linha, 1..7)?,
+                    tamanho,
+                    simbolo: simbolo.map(|simbolo| simbolo.trim().to_string()),
+                    negativa: linha.get(9..10) == Some("-"),
+                });
+            }
+
+            'E' => {
+                if linha.len() > 1 {
+                    programa.endereco_execucao = Some(ler_hex(linha, 1..7)?);
+                }
+
+                programas.extend(atual.take());
+            }
+
+            // Pares de nome com 6 caracteres e endereço
+            'D' => {
+                let mut posicao = 1;
+                while posicao < linha.len() {
+                    let nome = linha
+                        .get(posicao..posicao + 6)
+                        .with_context(|| format!("Registro incompleto: {}", linha))?;
+
+                    programa.definicoes.push((
+                        nome.trim().to_string(),
+                        ler_hex(linha, posicao + 6..posicao + 12)?,
+                    ));
+
+                    posicao += 12;
+                }
+            }
+
+            // Nomes com 6 caracteres, o último pode ter os espaços removidos
+            'R' => {
+                let nomes = linha.get(1..).unwrap_or_default().as_bytes().chunks(6);
+                for nome in nomes {
+                    let nome = String::from_utf8_lossy(nome).trim().to_string();
+                    programa.referencias.push(nome);
+                }
+            }
+            _ => return Err(anyhow!("Registro desconhecido: {}", tipo)),
+        }
+    }
+
+    if atual.is_some() {
+        return Err(anyhow!("Programa objeto sem registro E"));
+    }
+
+    Ok(programas)
+}
+
+/// Resultado da ligação de um ou mais programas objeto.
+#[derive(Debug, PartialEq)]
+pub struct Ligacao {
+    /// Tabela de símbolos externos (ESTAB), com as seções e os símbolos dos registros D
+    pub simbolos_externos: HashMap<String, usize>,
+    pub endereco_execucao: usize,
+    /// Tamanho total dos programas carregados
+    pub tamanho: usize,
+}
+
+/// Carregador de ligação de dois passos. As seções são colocadas uma após a outra a
+/// partir de `endereco_programa` (PROGADDR). O primeiro passo monta a tabela de símbolos
+/// externos e o segundo carrega os registros T, deslocados pela distância entre o
+/// endereço da seção e o seu endereço inicial, e aplica os registros M.
+///
+/// A execução começa no endereço do primeiro registro E que possuir um endereço, ou no
+/// início do primeiro programa.
+pub fn ligar(
+    memoria: &mut [u8],
+    programas: &[ProgramaObjeto],
+    endereco_programa: usize,
+) -> anyhow::Result<Ligacao> {
+    // Primeiro passo: endereço de cada seção (CSADDR) e tabela de símbolos externos
+    let mut simbolos_externos = HashMap::new();
+    let mut enderecos_secoes = Vec::with_capacity(programas.len());
+    let mut endereco_secao = endereco_programa;
+
+    for programa in programas {
+        let deslocamento = endereco_secao as i64 - programa.endereco_inicial as i64;
+        if simbolos_externos
+            .insert(programa.nome.clone(), endereco_secao)
+            .is_some()
+        {
+            return Err(anyhow!("Seção {} definida múltiplas vezes", programa.nome));
+        }
+
+        for (simbolo, endereco) in &programa.definicoes {
+            let endereco = relocar(*endereco, deslocamento)?;
+            if simbolos_externos
+                .insert(simbolo.clone(), endereco)
+                .is_some()
+            {
+                return Err(anyhow!(
+                    "Símbolo externo {} definido múltiplas vezes",
+                    simbolo
+                ));
+            }
+        }
+
+        enderecos_secoes.push(endereco_secao);
+        endereco_secao += programa.tamanho;
+    }
+
+    if endereco_secao > memoria.len() {
+        return Err(anyhow!(
+            "Programa possui tamanho maior que o possível de carregar"
+        ));
+    }
+
+    // Segundo passo: carregar o código e resolver as referências
+    let mut endereco_execucao = None;
+    for (programa, endereco_secao) in programas.iter().zip(enderecos_secoes) {
+        let deslocamento = endereco_secao as i64 - programa.endereco_inicial as i64;
+
+        for (endereco, bytes) in &programa.textos {
+            let endereco = relocar(*endereco, deslocamento)?;
+            memoria
+                .get_mut(endereco..endereco + bytes.len())
+                .context("Programa possui tamanho maior que o possível de carregar")?
+                .copy_from_slice(bytes);
+        }
+
+        for modificacao in &programa.modificacoes {
+            // Campos relativos à própria seção foram montados a partir do endereço inicial
+            let valor = match modificacao.simbolo.as_deref() {
+                None => deslocamento,
+                Some(simbolo) if simbolo == programa.nome => deslocamento,
+                Some(simbolo) => *simbolos_externos
+                    .get(simbolo)
+                    .with_context(|| format!("Símbolo externo não resolvido: {}", simbolo))?
+                    as i64,
+            };
+
+            let valor = if modificacao.negativa { -valor } else { valor };
+            modificar(
+                memoria,
+                relocar(modificacao.endereco, deslocamento)?,
+                modificacao.tamanho,
+                valor,
+            )?;
+        }
+
+        if endereco_execucao.is_none()
+            && let Some(endereco) = programa.endereco_execucao
+        {
+            endereco_execucao = Some(relocar(endereco, deslocamento)?);
+        }
+    }
+
+    Ok(Ligacao {
+        simbolos_externos,
+        endereco_execucao: endereco_execucao.unwrap_or(endereco_programa),
+        tamanho: endereco_secao - endereco_programa,
+    })
+}
+
+fn relocar(endereco: usize, deslocamento: i64) -> anyhow::Result<usize> {
+    usize::try_from(endereco as i64 + deslocamento).context("Endereço fora da memória")
+}
+
+/// Soma um valor a um campo da memória com o tamanho em meio bytes. Campos com um
+/// número ímpar de meio bytes começam na segunda metade do primeiro byte.
+pub fn modificar(
+    memoria: &mut [u8],
+    endereco: usize,
+    tamanho: usize,
+    valor: i64,
+) -> anyhow::Result<()> {
+    let bytes = memoria
+        .get_mut(endereco..endereco + tamanho.div_ceil(2))
+        .context("Registro de modificação fora da memória")?;
+
+    let campo = bytes
+        .iter()
+        .fold(0u64, |campo, byte| (campo << 8) | *byte as u64);
+
+    let mascara = (1u64 << (4 * tamanho)) - 1;
+    let modificado = (campo & mascara).wrapping_add_signed(valor) & mascara;
+    let campo = (campo & !mascara) | modificado;
+
+    let tamanho_bytes = bytes.len();
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (campo >> (8 * (tamanho_bytes - 1 - i))) as u8;
+    }
+
+    Ok(())
+}
+
+fn ler_hex(linha: &str, posicao: std::ops::Range<usize>) -> anyhow::Result<usize> {
+    let hex = linha
+        .get(posicao)
+        .with_context(|| format!("Registro incompleto: {}", linha))?;
+
+    usize::from_str_radix(hex, 16).with_context(|| format!("Número hexadecimal inválido: {}", hex))
+}
@@ -0,0 +1,168 @@
+//! Definição do conjunto de instruções da SIC/XE.
+//!
+//! A tabela `INSTRUCOES` é a única definição das instruções: o montador busca os
+//! mnemônicos nela, o decodificador e o desmontador descobrem o formato e os operandos
+//! pelo opcode e o executor verifica os privilégios e chama a função de execução da
+//! instrução decodificada. Uma instrução nova é só uma entrada aqui, com a função do
+//! executor que a executa.
+
+use crate::maquina::constantes::registradores::{A, B, L, S, SW, T, X};
+use crate::maquina::executor::{self, Executar};
+use crate::maquina::instrucao::Formato::{self, Dois, Tres, Um};
+use Operandos::{Memoria, Nenhum, Numero, Registrador, RegistradorNumero, Registradores};
+
+/// Operandos aceitos por uma instrução, como são escritos no assembly.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operandos {
+    /// Sem operando, como o FIX e o RSUB
+    Nenhum,
+    /// Um registrador, como o CLEAR A
+    Registrador,
+    /// Dois registradores, como o ADDR S,A
+    Registradores,
+    /// Um registrador e um número de bits de 1 a 16, como o SHIFTL A,4
+    RegistradorNumero,
+    /// Um número de 0 a 15, como o SVC 2
+    Numero,
+    /// Um endereço ou valor imediato, nos formatos 3 e 4
+    Memoria,
+}
+
+/// Definição de uma instrução da SIC/XE.
+#[derive(Debug)]
+pub struct DefinicaoInstrucao {
+    pub mnemonico: &'static str,
+    /// Opcode sem os bits n e i
+    pub opcode: u8,
+    /// Formato 1, 2 ou 3. As de formato 3 também podem ser usadas no formato 4 com o +
+    pub formato: Formato,
+    pub operandos: Operandos,
+    /// Só pode ser executada em modo supervisor
+    pub privilegiada: bool,
+    /// Executa a instrução depois de decodificada
+    pub executar: Executar,
+}
+
+/// As definições só existem na tabela `INSTRUCOES`, então duas definições são iguais
+/// quando são a mesma entrada dela.
+impl PartialEq for DefinicaoInstrucao {
+    fn eq(&self, outra: &Self) -> bool {
+        std::ptr::eq(self, outra)
+    }
+}
+
+impl Eq for DefinicaoInstrucao {}
+
+impl DefinicaoInstrucao {
+    /// Se a instrução pode ser usada no formato 4.
+    pub fn estendida(&self) -> bool {
+        self.formato == Formato::Tres
+    }
+}
+
+const fn definir(
+    mnemonico: &'static str,
+    opcode: u8,
+    formato: Formato,
+    operandos: Operandos,
+    executar: Executar,
+) -> DefinicaoInstrucao {
+    DefinicaoInstrucao {
+        mnemonico,
+        opcode,
+        formato,
+        operandos,
+        privilegiada: false,
+        executar,
+    }
+}
+
+const fn privilegiada(
+    mnemonico: &'static str,
+    opcode: u8,
+    formato: Formato,
+    operandos: Operandos,
+    executar: Executar,
+) -> DefinicaoInstrucao {
+    DefinicaoInstrucao {
+        privilegiada: true,
+        ..definir(mnemonico, opcode, formato, operandos, executar)
+    }
+}
+
+pub static INSTRUCOES: &[DefinicaoInstrucao] = &[
+    definir("ADD", 0x18, Tres, Memoria, executor::add),
+    definir("ADDF", 0x58, Tres, Memoria, executor::addf),
+    definir("ADDR", 0x90, Dois, Registradores, executor::addr),
+    definir("AND", 0x40, Tres, Memoria, executor::and),
+    definir("CLEAR", 0xB4, Dois, Registrador, executor::clear),
+    definir("COMP", 0x28, Tres, Memoria, executor::comp),
+    definir("COMPF", 0x88, Tres, Memoria, executor::compf),
+    definir("COMPR", 0xA0, Dois, Registradores, executor::compr),
+    definir("DIV", 0x24, Tres, Memoria, executor::div),
+    definir("DIVF", 0x64, Tres, Memoria, executor::divf),
+    definir("DIVR", 0x9C, Dois, Registradores, executor::divr),
+    definir("FIX", 0xC4, Um, Nenhum, executor::fix),
+    definir("FLOAT", 0xC0, Um, Nenhum, executor::float),
+    privilegiada("HIO", 0xF4, Um, Nenhum, executor::hio),
+    definir("J", 0x3C, Tres, Memoria, executor::j),
+    definir("JEQ", 0x30, Tres, Memoria, executor::jeq),
+    definir("JGT", 0x34, Tres, Memoria, executor::jgt),
+    definir("JLT", 0x38, Tres, Memoria, executor::jlt),
+    definir("JSUB", 0x48, Tres, Memoria, executor::jsub),
+    definir("LDA", 0x00, Tres, Memoria, executor::carregar::<A>),
+    definir("LDB", 0x68, Tres, Memoria, executor::carregar::<B>),
+    definir("LDCH", 0x50, Tres, Memoria, executor::ldch),
+    definir("LDF", 0x70, Tres, Memoria, executor::ldf),
+    definir("LDL", 0x08, Tres, Memoria, executor::carregar::<L>),
+    definir("LDS", 0x6C, Tres, Memoria, executor::carregar::<S>),
+    definir("LDT", 0x74, Tres, Memoria, executor::carregar::<T>),
+    definir("LDX", 0x04, Tres, Memoria, executor::carregar::<X>),
+    privilegiada("LPS", 0xD0, Tres, Memoria, executor::lps),
+    definir("MUL", 0x20, Tres, Memoria, executor::mul),
+    definir("MULF", 0x60, Tres, Memoria, executor::mulf),
+    definir("MULR", 0x98, Dois, Registradores, executor::mulr),
+    definir("NORM", 0xC8, Um, Nenhum, executor::norm),
+    definir("OR", 0x44, Tres, Memoria, executor::or),
+    privilegiada("RD", 0xD8, Tres, Memoria, executor::rd),
+    definir("RMO", 0xAC, Dois, Registradores, executor::rmo),
+    definir("RSUB", 0x4C, Tres, Nenhum, executor::rsub),
+    definir("SHIFTL", 0xA4, Dois, RegistradorNumero, executor::shiftl),
+    definir("SHIFTR", 0xA8, Dois, RegistradorNumero, executor::shiftr),
+    privilegiada("SIO", 0xF0, Um, Nenhum, executor::sio),
+    privilegiada("SSK", 0xEC, Tres, Memoria, executor::ssk),
+    definir("STA", 0x0C, Tres, Memoria, executor::guardar::<A>),
+    definir("STB", 0x78, Tres, Memoria, executor::guardar::<B>),
+    definir("STCH", 0x54, Tres, Memoria, executor::stch),
+    definir("STF", 0x80, Tres, Memoria, executor::stf),
+    privilegiada("STI", 0xD4, Tres, Memoria, executor::sti),
+    definir("STL", 0x14, Tres, Memoria, executor::guardar::<L>),
+    definir("STS", 0x7C, Tres, Memoria, executor::guardar::<S>),
+    privilegiada("STSW", 0xE8, Tres, Memoria, executor::guardar::<SW>),
+    definir("STT", 0x84, Tres, Memoria, executor::guardar::<T>),
+    definir("STX", 0x10, Tres, Memoria, executor::guardar::<X>),
+    definir("SUB", 0x1C, Tres, Memoria, executor::sub),
+    definir("SUBF", 0x5C, Tres, Memoria, executor::subf),
+    definir("SUBR", 0x94, Dois, Registradores, executor::subr),
+    definir("SVC", 0xB0, Dois, Numero, executor::svc),
+    privilegiada("TD", 0xE0, Tres, Memoria, executor::td),
+    privilegiada("TIO", 0xF8, Um, Nenhum, executor::tio),
+    definir("TIX", 0x2C, Tres, Memoria, executor::tix),
+    definir("TIXR", 0xB8, Dois, Registrador, executor::tixr),
+    privilegiada("WD", 0xDC, Tres, Memoria, executor::wd),
+];
+
+/// Busca a instrução pelo mnemônico, sem o + do formato 4.
+pub fn por_mnemonico(mnemonico: &str) -> Option<&'static DefinicaoInstrucao> {
+    INSTRUCOES
+        .iter()
+        .find(|definicao| definicao.mnemonico == mnemonico)
+}
+
+/// Busca a instrução pelo primeiro byte dela. Nos formatos 3 e 4 os bits n e i são
+/// ignorados, e nos formatos 1 e 2 eles devem ser zero.
+pub fn por_opcode(byte: u8) -> Option<&'static DefinicaoInstrucao> {
+    INSTRUCOES.iter().find(|definicao| {
+        definicao.opcode == byte & 0xFC && (definicao.formato == Formato::Tres || byte & 0x03 == 0)
+    })
+}
@@ -1,2 +1,2 @@
-pub mod opcodes;
 pub mod registradores;
+pub mod status;
@@ -5,5 +5,10 @@ pub const B: usize = 3;
 pub const S: usize = 4;
 pub const T: usize = 5;
 pub const F: usize = 6;
+/// Temporizador de intervalo, alterado somente pelo STI
+pub const I: usize = 7;
 pub const PC: usize = 8;
 pub const SW: usize = 9;
+
+/// Nomes dos registradores, indexados pelo número.
+pub const NOMES: [&str; 10] = ["A", "X", "L", "B", "S", "T", "F", "I", "PC", "SW"];
@@ -0,0 +1,17 @@
+//! Campos do registrador SW.
+
+/// Modo de execução, com o bit ligado a máquina está em modo supervisor
+pub const MODO_SUPERVISOR: u64 = 0x800000;
+
+/// Chave de proteção do processo em execução. A chave 0 pode escrever em qualquer bloco
+/// da memória, as outras somente nos blocos com a mesma chave.
+pub const MASCARA_CHAVE: u64 = 0x3C0000;
+
+/// Bits da máscara de interrupções. Com o bit ligado as interrupções da classe são
+/// permitidas, desligado elas ficam pendentes. As interrupções de SVC e de programa são
+/// causadas pela própria instrução e não podem ser mascaradas.
+pub const MASCARA_TEMPORIZADOR: u64 = 0x002000;
+pub const MASCARA_ENTRADA_SAIDA: u64 = 0x001000;
+
+/// Código da interrupção (ICODE), setado quando a interrupção acontece
+pub const MASCARA_ICODE: u64 = 0x0000FF;
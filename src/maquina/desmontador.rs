@@ -0,0 +1,302 @@
+//! Desmontador da SIC/XE, que transforma os bytes da memória, de um programa objeto ou
+//! de um arquivo .hex de volta em assembly.
+
+use crate::maquina::carregador;
+use crate::maquina::conjunto_instrucoes::Operandos;
+use crate::maquina::constantes::registradores;
+use crate::maquina::instrucao::{self, Enderecamento, Formato, Instrucao};
+use anyhow::{Context, anyhow};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Instrução desmontada, ou um byte que não é uma instrução válida.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinhaDesmontada {
+    pub endereco: u64,
+    pub bytes: Vec<u8>,
+    pub label: Option<String>,
+    /// Mnemônico com o + no formato 4, ou BYTE quando os bytes não são uma instrução
+    pub operacao: String,
+    /// Operando escrito como no montador, com #, @ e ,X
+    pub operando: String,
+    /// Endereço alvo dos formatos 3 e 4, sem a indexação. Não existe nos valores
+    /// imediatos que não são endereços
+    pub alvo: Option<u64>,
+}
+
+impl LinhaDesmontada {
+    /// Linha de assembly, com o label, a operação e o operando.
+    pub fn assembly(&self) -> String {
+        let linha = format!(
+            "{:<8} {:<7} {}",
+            self.label.as_deref().unwrap_or_default(),
+            self.operacao,
+            self.operando
+        );
+
+        linha.trim_end().to_string()
+    }
+}
+
+/// Endereço, código objeto, assembly e endereço alvo, como numa listagem.
+impl fmt::Display for LinhaDesmontada {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let codigo: String = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        write!(f, "{:06X}  {:<8}  ", self.endereco, codigo)?;
+        match self.alvo {
+            Some(alvo) => write!(f, "{:<28}  {:06X}", self.assembly(), alvo),
+            None => write!(f, "{}", self.assembly()),
+        }
+    }
+}
+
+/// Desmonta a memória de `inicio` até `fim`. Os símbolos, como os da tabela de símbolos
+/// externos, viram labels e substituem os endereços alvo. A base é usada pelas
+/// instruções relativas à base e é atualizada por cada LDB imediato, que normalmente
+/// acompanha a diretiva BASE.
+pub fn desmontar(
+    memoria: &[u8],
+    inicio: u64,
+    fim: u64,
+    base: Option<u64>,
+    simbolos: &HashMap<String, usize>,
+) -> Vec<LinhaDesmontada> {
+    let labels = labels(simbolos);
+    let memoria = &memoria[..(fim as usize).min(memoria.len())];
+    let mut base = base;
+    let mut linhas = Vec::new();
+    let mut endereco = inicio;
+
+    while (endereco as usize) < memoria.len() {
+        let (mut linha, tamanho) = match instrucao::decodificar(memoria, endereco) {
+            Ok(instrucao) => {
+                let linha = desmontar_instrucao(&instrucao, base, &labels);
+                if instrucao.definicao.mnemonico == "LDB"
+                    && instrucao.enderecamento() == Some(Enderecamento::Imediato)
+                {
+                    base = linha.alvo;
+                }
+
+                (linha, instrucao.tamanho())
+            }
+
+            // Bytes que não são instruções, como os dados do programa
+            Err(_) => {
+                let linha = LinhaDesmontada {
+                    endereco,
+                    bytes: Vec::new(),
+                    label: None,
+                    operacao: "BYTE".to_string(),
+                    operando: format!("X'{:02X}'", memoria[endereco as usize]),
+                    alvo: None,
+                };
+
+                (linha, 1)
+            }
+        };
+
+        linha.bytes = memoria[endereco as usize..(endereco + tamanho) as usize].to_vec();
+        linha.label = labels.get(&endereco).map(|label| label.to_string());
+        endereco += tamanho;
+        linhas.push(linha);
+    }
+
+    linhas
+}
+
+/// Desmonta os registros T de todos os programas de um texto objeto. Os endereços são
+/// os do próprio programa objeto, sem os registros M aplicados, e os nomes das seções
+/// e os símbolos dos registros D viram labels. As instruções de formato 4 modificadas
+/// por um símbolo externo mostram o nome dele no operando.
+pub fn desmontar_objeto(objeto: &str) -> anyhow::Result<Vec<LinhaDesmontada>> {
+    let mut linhas = Vec::new();
+    for programa in carregador::ler_programas(objeto)? {
+        let mut simbolos: HashMap<String, usize> = programa.definicoes.iter().cloned().collect();
+        simbolos.insert(programa.nome.clone(), programa.endereco_inicial);
+
+        let mut textos = programa.textos;
+        textos.sort_by_key(|(endereco, _)| *endereco);
+
+        let fim = textos
+            .iter()
+            .map(|(endereco, bytes)| endereco + bytes.len())
+            .max()
+            .unwrap_or_default();
+
+        let mut memoria = vec![0; fim];
+        for (endereco, bytes) in &textos {
+            memoria[*endereco..endereco + bytes.len()].copy_from_slice(bytes);
+        }
+
+        // Registros T seguidos são desmontados juntos, uma instrução pode estar dividida
+        // entre eles
+        let mut trechos: Vec<(usize, usize)> = Vec::new();
+        for (endereco, bytes) in &textos {
+            match trechos.last_mut() {
+                Some((_, fim)) if *fim == *endereco => *fim += bytes.len(),
+                _ => trechos.push((*endereco, endereco + bytes.len())),
+            }
+        }
+
+        for (inicio, fim) in trechos {
+            for mut linha in desmontar(&memoria, inicio as u64, fim as u64, None, &simbolos) {
+                // O endereço do formato 4 começa no segundo byte da instrução
+                let externo = programa.modificacoes.iter().find_map(|modificacao| {
+                    modificacao
+                        .simbolo
+                        .as_deref()
+                        .filter(|simbolo| *simbolo != programa.nome)
+                        .filter(|_| modificacao.endereco as u64 == linha.endereco + 1)
+                });
+
+                if let Some(simbolo) = externo
+                    && linha.operacao.starts_with('+')
+                {
+                    let indexado = linha.operando.ends_with(",X");
+                    linha.operando = linha
+                        .operando
+                        .chars()
+                        .take_while(|c| matches!(c, '#' | '@'))
+                        .collect();
+                    linha.operando.push_str(simbolo);
+                    if indexado {
+                        linha.operando.push_str(",X");
+                    }
+
+                    linha.alvo = None;
+                }
+
+                linhas.push(linha);
+            }
+        }
+    }
+
+    Ok(linhas)
+}
+
+/// Desmonta um arquivo .hex, com os bytes do programa em hexadecimal separados ou não
+/// por espaços, como se estivesse carregado no endereço informado.
+pub fn desmontar_hex(texto: &str, endereco: u64) -> anyhow::Result<Vec<LinhaDesmontada>> {
+    let digitos: String = texto.split_whitespace().collect();
+    if !digitos.len().is_multiple_of(2) {
+        return Err(anyhow!("Número ímpar de dígitos hexadecimais"));
+    }
+
+    let mut memoria = vec![0; endereco as usize];
+    for i in (0..digitos.len()).step_by(2) {
+        let byte = &digitos[i..i + 2];
+        memoria.push(
+            u8::from_str_radix(byte, 16)
+                .with_context(|| format!("Número hexadecimal inválido: {}", byte))?,
+        );
+    }
+
+    let fim = memoria.len() as u64;
+    Ok(desmontar(&memoria, endereco, fim, None, &HashMap::new()))
+}
+
+/// Labels de cada endereço. Com mais de um símbolo no mesmo endereço, o primeiro em
+/// ordem alfabética é usado.
+fn labels(simbolos: &HashMap<String, usize>) -> BTreeMap<u64, &str> {
+    let mut labels: BTreeMap<u64, &str> = BTreeMap::new();
+    for (nome, endereco) in simbolos {
+        let label = labels.entry(*endereco as u64).or_insert(nome);
+        if nome.as_str() < *label {
+            *label = nome;
+        }
+    }
+
+    labels
+}
+
+fn desmontar_instrucao(
+    instrucao: &Instrucao,
+    base: Option<u64>,
+    labels: &BTreeMap<u64, &str>,
+) -> LinhaDesmontada {
+    let definicao = instrucao.definicao;
+    let mut linha = LinhaDesmontada {
+        endereco: instrucao.endereco,
+        bytes: Vec::new(),
+        label: None,
+        operacao: definicao.mnemonico.to_string(),
+        operando: String::new(),
+        alvo: None,
+    };
+
+    let registrador = |numero: u8| {
+        registradores::NOMES
+            .get(numero as usize)
+            .map(|nome| nome.to_string())
+            .unwrap_or_else(|| numero.to_string())
+    };
+
+    match definicao.operandos {
+        Operandos::Nenhum => {}
+        Operandos::Registrador => linha.operando = registrador(instrucao.r1),
+        Operandos::Registradores => {
+            linha.operando = format!(
+                "{},{}",
+                registrador(instrucao.r1),
+                registrador(instrucao.r2)
+            )
+        }
+
+        // O r2 guarda o número de bits menos 1
+        Operandos::RegistradorNumero => {
+            linha.operando = format!("{},{}", registrador(instrucao.r1), instrucao.r2 + 1)
+        }
+
+        Operandos::Numero => linha.operando = instrucao.r1.to_string(),
+        Operandos::Memoria => {
+            // A indexação aparece como ,X e não entra no endereço alvo
+            let mut registradores = [0; 10];
+            registradores[registradores::B] = base.unwrap_or_default();
+            let alvo = instrucao
+                .endereco_alvo(&registradores)
+                .filter(|_| !instrucao.flags.b || base.is_some());
+
+            let flags = instrucao.flags;
+            let endereco = flags.p || flags.b || instrucao.formato == Formato::Quatro;
+            let (prefixo, valor) = match instrucao.enderecamento() {
+                Some(Enderecamento::Imediato) if !endereco => {
+                    ("#", instrucao.deslocamento.to_string())
+                }
+                Some(Enderecamento::Imediato) => ("#", nome_endereco(alvo, instrucao, labels)),
+                Some(Enderecamento::Indireto) => ("@", nome_endereco(alvo, instrucao, labels)),
+                _ => ("", nome_endereco(alvo, instrucao, labels)),
+            };
+
+            if instrucao.enderecamento() != Some(Enderecamento::Imediato) || endereco {
+                linha.alvo = alvo;
+            }
+
+            linha.operando = format!("{}{}", prefixo, valor);
+            if flags.x {
+                linha.operando.push_str(",X");
+            }
+        }
+    }
+
+    if instrucao.formato == Formato::Quatro {
+        linha.operacao.insert(0, '+');
+    }
+
+    linha
+}
+
+/// Label do endereço alvo ou o próprio endereço em hexadecimal. Sem a base o alvo não é
+/// conhecido e o deslocamento é mostrado relativo a ela.
+fn nome_endereco(alvo: Option<u64>, instrucao: &Instrucao, labels: &BTreeMap<u64, &str>) -> String {
+    match alvo {
+        Some(alvo) => match labels.get(&alvo) {
+            Some(label) => label.to_string(),
+            None => format!("{:04X}", alvo),
+        },
+        None => format!("(B)+{:03X}", instrucao.deslocamento),
+    }
+}
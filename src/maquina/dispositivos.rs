@@ -0,0 +1,171 @@
+use anyhow::Context;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read, Write};
+use std::rc::Rc;
+
+/// Dispositivo de entrada e saída, acessado pelas instruções TD, RD e WD.
+pub trait Dispositivo {
+    /// Retorna se o dispositivo está pronto para transferir dados (TD).
+    fn testar(&mut self) -> bool {
+        true
+    }
+
+    /// Lê um byte (RD). Retorna None quando ainda não há dados, e a instrução
+    /// fica esperando até que eles existam.
+    fn ler(&mut self) -> anyhow::Result<Option<u8>>;
+
+    /// Escreve um byte (WD).
+    fn escrever(&mut self, byte: u8) -> anyhow::Result<()>;
+}
+
+/// Tabela de dispositivos da máquina, indexada pelo número do dispositivo.
+///
+/// Os dispositivos 00 e 01 são a entrada e a saída padrão. Números sem um dispositivo
+/// conectado usam o arquivo `NN.dev` do diretório atual, como `05.dev`.
+pub struct Dispositivos {
+    tabela: HashMap<u8, Box<dyn Dispositivo>>,
+}
+
+impl Default for Dispositivos {
+    fn default() -> Self {
+        let mut tabela: HashMap<u8, Box<dyn Dispositivo>> = HashMap::new();
+        tabela.insert(0x00, Box::new(Terminal));
+        tabela.insert(0x01, Box::new(Terminal));
+        Self { tabela }
+    }
+}
+
+impl Dispositivos {
+    /// Conecta um dispositivo, substituindo o que estava no mesmo número.
+    pub fn conectar(&mut self, numero: u8, dispositivo: Box<dyn Dispositivo>) {
+        self.tabela.insert(numero, dispositivo);
+    }
+
+    /// Desconecta o dispositivo do número, que volta a usar o arquivo `NN.dev`.
+    pub fn desconectar(&mut self, numero: u8) {
+        self.tabela.remove(&numero);
+    }
+
+    /// Retorna o dispositivo com o número, conectando o arquivo dele caso não exista.
+    pub fn obter(&mut self, numero: u8) -> &mut dyn Dispositivo {
+        self.tabela
+            .entry(numero)
+            .or_insert_with(|| Box::new(Arquivo::new(numero)))
+            .as_mut()
+    }
+}
+
+/// Fila de bytes em memória. Os bytes escritos pela máquina ficam na saída e os lidos
+/// são retirados da entrada. Clones compartilham as mesmas filas, então quem conecta
+/// a fila na máquina pode continuar usando um clone para acompanhar o dispositivo.
+#[derive(Clone, Default)]
+pub struct Fila {
+    entrada: Rc<RefCell<VecDeque<u8>>>,
+    saida: Rc<RefCell<VecDeque<u8>>>,
+}
+
+impl Fila {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adiciona bytes para a máquina ler.
+    pub fn adicionar_entrada(&self, bytes: &[u8]) {
+        self.entrada.borrow_mut().extend(bytes);
+    }
+
+    /// Retira todos os bytes escritos pela máquina.
+    pub fn retirar_saida(&self) -> Vec<u8> {
+        self.saida.borrow_mut().drain(..).collect()
+    }
+}
+
+impl Dispositivo for Fila {
+    fn ler(&mut self) -> anyhow::Result<Option<u8>> {
+        Ok(self.entrada.borrow_mut().pop_front())
+    }
+
+    fn escrever(&mut self, byte: u8) -> anyhow::Result<()> {
+        self.saida.borrow_mut().push_back(byte);
+        Ok(())
+    }
+}
+
+/// Arquivo em disco com o nome `NN.dev`, onde NN é o número do dispositivo em
+/// hexadecimal. Os bytes escritos são adicionados ao final do arquivo e a leitura
+/// depois do final do arquivo retorna 00.
+pub struct Arquivo {
+    caminho: String,
+    leitor: Option<BufReader<File>>,
+    escritor: Option<File>,
+}
+
+impl Arquivo {
+    pub fn new(numero: u8) -> Self {
+        Self {
+            caminho: format!("{:02X}.dev", numero),
+            leitor: None,
+            escritor: None,
+        }
+    }
+}
+
+impl Dispositivo for Arquivo {
+    fn ler(&mut self) -> anyhow::Result<Option<u8>> {
+        let leitor = match &mut self.leitor {
+            Some(leitor) => leitor,
+            leitor => leitor
+                .insert(BufReader::new(File::open(&self.caminho).with_context(
+                    || format!("Erro ao abrir o dispositivo {}", self.caminho),
+                )?)),
+        };
+
+        let mut byte = [0];
+        let lidos = leitor
+            .read(&mut byte)
+            .with_context(|| format!("Erro ao ler o dispositivo {}", self.caminho))?;
+
+        Ok(Some(if lidos == 0 { 0 } else { byte[0] }))
+    }
+
+    fn escrever(&mut self, byte: u8) -> anyhow::Result<()> {
+        let escritor = match &mut self.escritor {
+            Some(escritor) => escritor,
+            escritor => escritor.insert(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&self.caminho)
+                    .with_context(|| format!("Erro ao abrir o dispositivo {}", self.caminho))?,
+            ),
+        };
+
+        escritor
+            .write_all(&[byte])
+            .with_context(|| format!("Erro ao escrever no dispositivo {}", self.caminho))
+    }
+}
+
+/// Entrada e saída padrão do processo. A leitura espera até o usuário digitar algo.
+pub struct Terminal;
+
+impl Dispositivo for Terminal {
+    fn ler(&mut self) -> anyhow::Result<Option<u8>> {
+        let mut byte = [0];
+        let lidos = std::io::stdin()
+            .read(&mut byte)
+            .context("Erro ao ler a entrada padrão")?;
+
+        Ok(Some(if lidos == 0 { 0 } else { byte[0] }))
+    }
+
+    fn escrever(&mut self, byte: u8) -> anyhow::Result<()> {
+        let mut saida = std::io::stdout();
+        saida
+            .write_all(&[byte])
+            .and_then(|_| saida.flush())
+            .context("Erro ao escrever na saída padrão")
+    }
+}
@@ -1,6 +1,19 @@
-use crate::maquina::constantes::{opcodes, registradores};
+use crate::maquina::constantes::{registradores, status};
+use crate::maquina::dispositivos::Dispositivos;
+use crate::maquina::falha::Falha;
+use crate::maquina::instrucao::{self, Enderecamento, Instrucao};
+use crate::maquina::interrupcoes::{self, Interrupcao};
+use crate::maquina::ponto_flutuante;
 use anyhow::{Context, anyhow};
-use bitreader::BitReader;
+use std::cmp::Ordering;
+
+/// Bits do código de condição (CC) no SW. Igual é 00, maior é 01 e menor é 11.
+const MASCARA_CC: u64 = 0x030000;
+const CC_MAIOR: u64 = 0x010000;
+const CC_MENOR: u64 = 0x030000;
+
+/// Tamanho dos blocos de memória que compartilham uma chave de proteção.
+pub const TAMANHO_BLOCO: usize = 2048;
 
 /// Seta o valor de um registrador.
 /// Necessário usar esta função para eles terem o tamanho correto.
@@ -15,605 +28,648 @@ pub fn set_registrador(registradores: &mut [u64], numero: usize, valor: u64) {
     }
 }
 
-/// Lê da memória, decodifica e executa uma instrução.
-pub fn executar_instrucao(registradores: &mut [u64], memoria: &mut [u8]) -> anyhow::Result<()> {
-    let Some(proximas) = memoria.get(registradores[registradores::PC] as usize..) else {
-        return Err(anyhow!("PC não aponta para um endereço válido"));
+/// Interpreta um valor de 24 bits como um número com sinal em complemento de 2.
+fn com_sinal(valor: u64) -> i64 {
+    let valor = (valor & 0xFFFFFF) as i64;
+    if valor & 0x800000 != 0 {
+        valor - 0x1000000
+    } else {
+        valor
+    }
+}
+
+/// Compara dois valores de 24 bits com sinal e seta o CC do SW com o resultado.
+fn comparar(registradores: &mut [u64], primeiro: u64, segundo: u64) {
+    setar_cc(registradores, com_sinal(primeiro).cmp(&com_sinal(segundo)));
+}
+
+fn setar_cc(registradores: &mut [u64], ordem: Ordering) {
+    let cc = match ordem {
+        Ordering::Less => CC_MENOR,
+        Ordering::Equal => 0,
+        Ordering::Greater => CC_MAIOR,
     };
 
-    let mut instrucao = BitReader::new(proximas);
-    let mut tamanho_instrucao = 2;
-
-    if let Ok(opcode) = instrucao.read_u8(8) {
-        match opcode {
-            opcodes::ADDR => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let registrador_destino = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador2 = registradores
-                    .get(registrador_destino as usize)
-                    .context("Registrador não encontrado")?;
-
-                set_registrador(
-                    registradores,
-                    registrador_destino as usize,
-                    registrador1 + registrador2,
-                );
-            }
-
-            opcodes::CLEAR => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                set_registrador(registradores, registrador1 as usize, 0);
-            }
-
-            opcodes::COMPR => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let registrador2 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador2 = registradores
-                    .get(registrador2 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let sw = registradores[registradores::SW];
-                if registrador1 > registrador2 {
-                    // Setar CC para 01
-                    set_registrador(registradores, registradores::SW, sw & 0xFDFFFF);
-                    set_registrador(registradores, registradores::SW, sw | 0x010000);
-                } else if registrador1 < registrador2 {
-                    // Setar CC para 11 (-1)
-                    set_registrador(registradores, registradores::SW, sw | 0x018000);
-                } else {
-                    // Setar CC para 00
-                    set_registrador(registradores, registradores::SW, sw & 0xFCFFFF);
-                }
-            }
-
-            opcodes::DIVR => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let registrador_destino = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador2 = registradores
-                    .get(registrador_destino as usize)
-                    .context("Registrador não encontrado")?;
-
-                set_registrador(
-                    registradores,
-                    registrador_destino as usize,
-                    registrador1 / registrador2,
-                );
-            }
-
-            opcodes::MULR => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let registrador_destino = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador2 = registradores
-                    .get(registrador_destino as usize)
-                    .context("Registrador não encontrado")?;
-
-                set_registrador(
-                    registradores,
-                    registrador_destino as usize,
-                    registrador1 * registrador2,
-                );
-            }
-
-            opcodes::RMO => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let registrador2 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                set_registrador(registradores, registrador2 as usize, *registrador1);
-            }
-
-            opcodes::SHIFTL => {
-                let registrador_destino = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador_destino as usize)
-                    .context("Registrador não encontrado")?;
-
-                let bits = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                set_registrador(
-                    registradores,
-                    registrador_destino as usize,
-                    registrador1 << bits,
-                );
-            }
-
-            opcodes::SHIFTR => {
-                let registrador_destino = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador_destino as usize)
-                    .context("Registrador não encontrado")?;
-
-                let bits = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                set_registrador(
-                    registradores,
-                    registrador_destino as usize,
-                    registrador1 >> bits,
-                );
-            }
-
-            opcodes::SUBR => {
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let registrador_destino = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador2 = registradores
-                    .get(registrador_destino as usize)
-                    .context("Registrador não encontrado")?;
-
-                set_registrador(
-                    registradores,
-                    registrador_destino as usize,
-                    registrador1 - registrador2,
-                );
-            }
-
-            opcodes::TIXR => {
-                let x = registradores[registradores::X];
-                set_registrador(registradores, registradores::X, x + 1);
-
-                let registrador1 = instrucao.read_u8(4).context("Erro ao ler instrução")?;
-                let registrador1 = registradores
-                    .get(registrador1 as usize)
-                    .context("Registrador não encontrado")?;
-
-                let sw = registradores[registradores::SW];
-                if x > *registrador1 {
-                    // Setar CC para 01
-                    set_registrador(registradores, registradores::SW, sw & 0xFDFFFF);
-                    set_registrador(registradores, registradores::SW, sw | 0x010000);
-                } else if x < *registrador1 {
-                    // Setar CC para 11 (-1)
-                    set_registrador(registradores, registradores::SW, sw | 0x018000);
-                } else {
-                    // Setar CC para 00
-                    set_registrador(registradores, registradores::SW, sw & 0xFCFFFF);
-                }
-            }
-
-            _ => {
-                // Últimos 2 bits
-                let modo_enderecamento = opcode & 0x03;
-                let flags = instrucao
-                    .read_u8(if modo_enderecamento == 0 { 1 } else { 4 })
-                    .context("Erro ao ler flags da instrução")?;
-
-                // Primeiros 6 bits
-                let opcode = opcode & 0xFC;
-                let valor = match modo_enderecamento {
-                    // Direto formato SIC, verificar somente flag x
-                    0 => {
-                        let endereco = instrucao
-                            .read_u64(15)
-                            .context("Erro ao ler valor da instrução")?;
-
-                        let endereco = if flags == 0 {
-                            endereco
-                        } else {
-                            registradores[registradores::X] + endereco
-                        };
-
-                        let byte1 = *memoria
-                            .get(endereco as usize)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte2 = *memoria
-                            .get(endereco as usize + 1)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte3 = *memoria
-                            .get(endereco as usize + 2)
-                            .context("Endereço de memória inválido")?;
-
-                        tamanho_instrucao = 3;
-                        u64::from_be_bytes([0, 0, 0, 0, 0, byte1, byte2, byte3])
-                    }
-
-                    // Imediato
-                    1 => match flags {
-                        0 => {
-                            tamanho_instrucao = 3;
-                            instrucao
-                                .read_u64(12)
-                                .context("Erro ao ler valor da instrução")?
-                        }
-
-                        1 => {
-                            tamanho_instrucao = 4;
-                            instrucao
-                                .read_u64(20)
-                                .context("Erro ao ler valor da instrução")?
-                        }
-
-                        2 => {
-                            let valor = instrucao
-                                .read_u64(12)
-                                .context("Erro ao ler valor da instrução")?;
-
-                            tamanho_instrucao = 3;
-                            registradores[registradores::PC] + valor
-                        }
-
-                        4 => {
-                            let valor = instrucao
-                                .read_u64(12)
-                                .context("Erro ao ler valor da instrução")?;
-
-                            tamanho_instrucao = 3;
-                            registradores[registradores::B] + valor
-                        }
-
-                        _ => return Err(anyhow!("Modo de endereçamento inválido")),
-                    },
-
-                    // Indireto
-                    2 => {
-                        let endereco_indireto = match flags {
-                            0 => {
-                                tamanho_instrucao = 3;
-                                instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?
-                            }
-
-                            1 => {
-                                tamanho_instrucao = 4;
-                                instrucao
-                                    .read_u64(20)
-                                    .context("Erro ao ler endereço da instrução")?
-                            }
-
-                            2 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::PC] + endereco
-                            }
-
-                            4 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::B] + endereco
-                            }
-
-                            _ => return Err(anyhow!("Modo de endereçamento inválido")),
-                        };
-
-                        let byte1 = *memoria
-                            .get(endereco_indireto as usize)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte2 = *memoria
-                            .get(endereco_indireto as usize + 1)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte3 = *memoria
-                            .get(endereco_indireto as usize + 2)
-                            .context("Endereço de memória inválido")?;
-
-                        let endereco_dado =
-                            u64::from_be_bytes([0, 0, 0, 0, 0, byte1, byte2, byte3]);
-
-                        let byte1 = *memoria
-                            .get(endereco_dado as usize)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte2 = *memoria
-                            .get(endereco_dado as usize + 1)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte3 = *memoria
-                            .get(endereco_dado as usize + 2)
-                            .context("Endereço de memória inválido")?;
-
-                        u64::from_be_bytes([0, 0, 0, 0, 0, byte1, byte2, byte3])
-                    }
-
-                    // Direto
-                    3 => {
-                        let endereco = match flags {
-                            0 => {
-                                tamanho_instrucao = 3;
-                                instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?
-                            }
-
-                            1 => {
-                                tamanho_instrucao = 4;
-                                instrucao
-                                    .read_u64(20)
-                                    .context("Erro ao ler endereço da instrução")?
-                            }
-
-                            2 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::PC] + endereco
-                            }
-
-                            4 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::B] + endereco
-                            }
-
-                            8 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::X] + endereco
-                            }
-
-                            9 => {
-                                let endereco = instrucao
-                                    .read_u64(20)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 4;
-                                registradores[registradores::X] + endereco
-                            }
-
-                            10 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::PC]
-                                    + registradores[registradores::X]
-                                    + endereco
-                            }
-
-                            12 => {
-                                let endereco = instrucao
-                                    .read_u64(12)
-                                    .context("Erro ao ler endereço da instrução")?;
-
-                                tamanho_instrucao = 3;
-                                registradores[registradores::B]
-                                    + registradores[registradores::X]
-                                    + endereco
-                            }
-
-                            _ => return Err(anyhow!("Modo de endereçamento inválido")),
-                        };
-
-                        let byte1 = *memoria
-                            .get(endereco as usize)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte2 = *memoria
-                            .get(endereco as usize + 1)
-                            .context("Endereço de memória inválido")?;
-
-                        let byte3 = *memoria
-                            .get(endereco as usize + 2)
-                            .context("Endereço de memória inválido")?;
-
-                        u64::from_be_bytes([0, 0, 0, 0, 0, byte1, byte2, byte3])
-                    }
-
-                    _ => return Err(anyhow!("Modo de endereçamento inválido")),
-                };
-
-                match opcode {
-                    opcodes::ADD => set_registrador(
-                        registradores,
-                        registradores::A,
-                        registradores[registradores::A] + valor,
-                    ),
-
-                    opcodes::AND => set_registrador(
-                        registradores,
-                        registradores::A,
-                        registradores[registradores::A] & valor,
-                    ),
-
-                    opcodes::OR => set_registrador(
-                        registradores,
-                        registradores::A,
-                        registradores[registradores::A] | valor,
-                    ),
-
-                    opcodes::RSUB => set_registrador(
-                        registradores,
-                        registradores::PC,
-                        registradores[registradores::L],
-                    ),
-
-                    opcodes::J => set_registrador(registradores, registradores::PC, valor),
-                    opcodes::JEQ => {
-                        let cc = registradores[registradores::SW] & 0x018000;
-                        if cc == 0 {
-                            set_registrador(registradores, registradores::PC, valor);
-                        }
-                    }
-
-                    opcodes::JGT => {
-                        let cc = registradores[registradores::SW] & 0x018000;
-                        if cc == 0x010000 {
-                            set_registrador(registradores, registradores::PC, valor);
-                        }
-                    }
-
-                    opcodes::JLT => {
-                        let cc = registradores[registradores::SW] & 0x018000;
-                        if cc == 0x018000 {
-                            set_registrador(registradores, registradores::PC, valor);
-                        }
-                    }
-
-                    opcodes::JSUB => {
-                        set_registrador(
-                            registradores,
-                            registradores::L,
-                            registradores[registradores::PC],
-                        );
-
-                        set_registrador(registradores, registradores::PC, valor);
-                    }
-
-                    opcodes::LDA => set_registrador(registradores, registradores::A, valor),
-                    opcodes::LDB => set_registrador(registradores, registradores::B, valor),
-                    opcodes::LDCH => set_registrador(registradores, registradores::A, valor & 0xFF),
-                    opcodes::LDL => set_registrador(registradores, registradores::L, valor),
-                    opcodes::LDS => set_registrador(registradores, registradores::S, valor),
-                    opcodes::LDT => set_registrador(registradores, registradores::T, valor),
-                    opcodes::LDX => set_registrador(registradores, registradores::X, valor),
-
-                    opcodes::STA => {
-                        let registrador_bytes = registradores[registradores::A].to_be_bytes();
-                        memoria
-                            .get_mut(valor as usize..valor as usize + 3)
-                            .context("Endereço de store inválido")?
-                            .copy_from_slice(&registrador_bytes[5..]);
-                    }
-
-                    opcodes::STB => {
-                        let registrador_bytes = registradores[registradores::B].to_be_bytes();
-                        memoria
-                            .get_mut(valor as usize..valor as usize + 3)
-                            .context("Endereço de store inválido")?
-                            .copy_from_slice(&registrador_bytes[5..]);
-                    }
-
-                    opcodes::STCH => {
-                        let registrador_bytes = registradores[registradores::A].to_be_bytes();
-                        let valor_endereco = memoria
-                            .get_mut(valor as usize)
-                            .context("Endereço de store inválido")?;
-
-                        *valor_endereco = registrador_bytes.last().copied().unwrap_or(0);
-                    }
-
-                    opcodes::STL => {
-                        let registrador_bytes = registradores[registradores::L].to_be_bytes();
-                        memoria
-                            .get_mut(valor as usize..valor as usize + 3)
-                            .context("Endereço de store inválido")?
-                            .copy_from_slice(&registrador_bytes[5..]);
-                    }
-
-                    opcodes::STS => {
-                        let registrador_bytes = registradores[registradores::S].to_be_bytes();
-                        memoria
-                            .get_mut(valor as usize..valor as usize + 3)
-                            .context("Endereço de store inválido")?
-                            .copy_from_slice(&registrador_bytes[5..]);
-                    }
-
-                    opcodes::STT => {
-                        let registrador_bytes = registradores[registradores::T].to_be_bytes();
-                        memoria
-                            .get_mut(valor as usize..valor as usize + 3)
-                            .context("Endereço de store inválido")?
-                            .copy_from_slice(&registrador_bytes[5..]);
-                    }
-
-                    opcodes::STX => {
-                        let registrador_bytes = registradores[registradores::X].to_be_bytes();
-                        memoria
-                            .get_mut(valor as usize..valor as usize + 3)
-                            .context("Endereço de store inválido")?
-                            .copy_from_slice(&registrador_bytes[5..]);
-                    }
-
-                    opcodes::TIX => {
-                        let x = registradores[registradores::X];
-                        set_registrador(registradores, registradores::X, x + 1);
-
-                        let sw = registradores[registradores::SW];
-                        if x > valor {
-                            // Setar CC para 01
-                            set_registrador(registradores, registradores::SW, sw & 0xFDFFFF);
-                            set_registrador(registradores, registradores::SW, sw | 0x010000);
-                        } else if x < valor {
-                            // Setar CC para 11 (-1)
-                            set_registrador(registradores, registradores::SW, sw | 0x018000);
-                        } else {
-                            // Setar CC para 00
-                            set_registrador(registradores, registradores::SW, sw & 0xFCFFFF);
-                        }
-                    }
-
-                    opcodes::COMP => {
-                        let acumulador = registradores[registradores::A];
-                        let sw = registradores[registradores::SW];
-
-                        if acumulador > valor {
-                            // Setar CC para 01
-                            set_registrador(registradores, registradores::SW, sw & 0xFDFFFF);
-                            set_registrador(registradores, registradores::SW, sw | 0x010000);
-                        } else if acumulador < valor {
-                            // Setar CC para 11 (-1)
-                            set_registrador(registradores, registradores::SW, sw | 0x018000);
-                        } else {
-                            // Setar CC para 00
-                            set_registrador(registradores, registradores::SW, sw & 0xFCFFFF);
-                        }
-                    }
-
-                    opcodes::DIV => set_registrador(
-                        registradores,
-                        registradores::A,
-                        registradores[registradores::A] / valor,
-                    ),
-
-                    opcodes::MUL => set_registrador(
-                        registradores,
-                        registradores::A,
-                        registradores[registradores::A] * valor,
-                    ),
-
-                    opcodes::SUB => set_registrador(
-                        registradores,
-                        registradores::A,
-                        registradores[registradores::A] - valor,
-                    ),
-
-                    _ => return Err(anyhow!("Instrução inválida")),
-                }
-            }
+    let sw = registradores[registradores::SW] & !MASCARA_CC;
+    set_registrador(registradores, registradores::SW, sw | cc);
+}
+
+/// Retorna uma falha caso a máquina não esteja em modo supervisor.
+fn verificar_supervisor(registradores: &[u64]) -> anyhow::Result<()> {
+    if registradores[registradores::SW] & status::MODO_SUPERVISOR == 0 {
+        return Err(Falha::InstrucaoPrivilegiada.into());
+    }
+
+    Ok(())
+}
+
+/// Divisão com sinal, arredondada em direção ao zero.
+fn dividir(dividendo: u64, divisor: u64) -> anyhow::Result<u64> {
+    if com_sinal(divisor) == 0 {
+        return Err(Falha::DivisaoPorZero.into());
+    }
+
+    Ok((com_sinal(dividendo) / com_sinal(divisor)) as u64)
+}
+
+/// Lê uma palavra de 3 bytes da memória.
+pub fn ler_palavra(memoria: &[u8], endereco: u64) -> anyhow::Result<u64> {
+    ler_bytes(memoria, endereco, 3)
+}
+
+/// Lê um número de ponto flutuante de 6 bytes da memória.
+pub fn ler_float(memoria: &[u8], endereco: u64) -> anyhow::Result<u64> {
+    ler_bytes(memoria, endereco, 6)
+}
+
+fn ler_bytes(memoria: &[u8], endereco: u64, tamanho: usize) -> anyhow::Result<u64> {
+    let bytes = memoria
+        .get(endereco as usize..endereco as usize + tamanho)
+        .context("Endereço de memória inválido")?;
+
+    Ok(bytes
+        .iter()
+        .fold(0, |valor, byte| (valor << 8) | *byte as u64))
+}
+
+/// Escreve uma palavra de 3 bytes na memória.
+pub fn escrever_palavra(memoria: &mut [u8], endereco: u64, valor: u64) -> anyhow::Result<()> {
+    memoria
+        .get_mut(endereco as usize..endereco as usize + 3)
+        .context("Endereço de store inválido")?
+        .copy_from_slice(&valor.to_be_bytes()[5..]);
+
+    Ok(())
+}
+
+/// Escreve os bytes na memória. Com uma chave de proteção no SW, todos os blocos
+/// alterados devem ter a mesma chave, senão a escrita falha sem alterar a memória.
+fn armazenar(
+    registradores: &[u64],
+    memoria: &mut [u8],
+    chaves: &[u8],
+    endereco: u64,
+    bytes: &[u8],
+) -> anyhow::Result<()> {
+    let inicio = endereco as usize;
+    let destino = memoria
+        .get_mut(inicio..inicio + bytes.len())
+        .context("Endereço de store inválido")?;
+
+    let chave = (registradores[registradores::SW] & status::MASCARA_CHAVE) >> 18;
+    let blocos = inicio / TAMANHO_BLOCO..=(inicio + bytes.len() - 1) / TAMANHO_BLOCO;
+    if chave != 0 && chaves[blocos].iter().any(|bloco| *bloco as u64 != chave) {
+        return Err(Falha::ProtecaoMemoria.into());
+    }
+
+    destino.copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Função que executa uma instrução, indicada na definição dela no conjunto de
+/// instruções.
+pub type Executar = fn(&mut Execucao) -> anyhow::Result<()>;
+
+/// Operando de uma instrução de formato 3 ou 4.
+enum Operando {
+    /// Formatos 1 e 2, que não acessam a memória
+    Nenhum,
+    /// Endereçamento imediato, com o valor do operando
+    Imediato(u64),
+    /// Endereço alvo, já resolvido no endereçamento indireto
+    Endereco(u64),
+}
+
+/// Estado da máquina que uma instrução pode alterar, com o operando já resolvido.
+pub struct Execucao<'a> {
+    registradores: &'a mut [u64],
+    memoria: &'a mut [u8],
+    dispositivos: &'a mut Dispositivos,
+    chaves: &'a mut [u8],
+    pendentes: &'a mut Vec<Interrupcao>,
+    operando: Operando,
+    /// Registradores do formato 2
+    r1: u8,
+    r2: u8,
+    /// Marcado pelas instruções que devem ser executadas de novo, como o RD esperando
+    /// dados
+    repetir: bool,
+}
+
+impl Execucao<'_> {
+    fn get(&self, numero: usize) -> u64 {
+        self.registradores[numero]
+    }
+
+    fn set(&mut self, numero: usize, valor: u64) {
+        set_registrador(self.registradores, numero, valor);
+    }
+
+    /// Valor de um registrador usado por uma instrução de formato 2.
+    fn registrador(&self, numero: u8) -> anyhow::Result<u64> {
+        if numero as usize == registradores::I {
+            return Err(anyhow!("Registrador não encontrado"));
         }
+
+        self.registradores
+            .get(numero as usize)
+            .copied()
+            .context("Registrador não encontrado")
     }
 
-    set_registrador(
+    /// Valor do operando: o próprio operando no endereçamento imediato ou a palavra no
+    /// endereço alvo.
+    fn valor(&self) -> anyhow::Result<u64> {
+        match self.operando {
+            Operando::Imediato(valor) => Ok(valor),
+            Operando::Endereco(endereco) => ler_palavra(self.memoria, endereco),
+            Operando::Nenhum => Err(anyhow!("Instrução sem operando")),
+        }
+    }
+
+    /// Endereço alvo das instruções que acessam a memória diretamente, como as de ponto
+    /// flutuante, que sempre leem ou escrevem 6 bytes.
+    fn endereco(&self) -> anyhow::Result<u64> {
+        match self.operando {
+            Operando::Endereco(endereco) => Ok(endereco),
+            _ => Err(anyhow!("Instrução não aceita endereçamento imediato")),
+        }
+    }
+
+    /// Destino dos desvios, que no endereçamento imediato é o próprio operando.
+    fn destino(&self) -> u64 {
+        match self.operando {
+            Operando::Endereco(valor) | Operando::Imediato(valor) => valor,
+            Operando::Nenhum => 0,
+        }
+    }
+
+    /// Byte do operando, no endereço alvo ou o próprio valor imediato.
+    fn byte(&self) -> anyhow::Result<u64> {
+        match self.operando {
+            Operando::Endereco(endereco) => ler_bytes(self.memoria, endereco, 1),
+            _ => Ok(self.destino() & 0xFF),
+        }
+    }
+
+    /// Guarda os últimos bytes do registrador no endereço alvo.
+    fn guardar_bytes(&mut self, numero: usize, tamanho: usize) -> anyhow::Result<()> {
+        let endereco = self.endereco()?;
+        let bytes = self.registradores[numero].to_be_bytes();
+        armazenar(
+            self.registradores,
+            self.memoria,
+            self.chaves,
+            endereco,
+            &bytes[8 - tamanho..],
+        )
+    }
+
+    /// Desvia para o destino quando o CC do SW for o informado.
+    fn desviar_se(&mut self, cc: u64) {
+        if self.get(registradores::SW) & MASCARA_CC == cc {
+            self.set(registradores::PC, self.destino());
+        }
+    }
+
+    /// Executa uma operação de ponto flutuante entre o F e o número no endereço alvo.
+    fn operar_float(
+        &mut self,
+        operacao: impl Fn(f64, f64) -> anyhow::Result<f64>,
+    ) -> anyhow::Result<()> {
+        let f = ponto_flutuante::para_f64(self.get(registradores::F));
+        let operando = ponto_flutuante::para_f64(ler_float(self.memoria, self.endereco()?)?);
+        let resultado = ponto_flutuante::de_f64(operacao(f, operando)?)?;
+
+        self.set(registradores::F, resultado);
+        Ok(())
+    }
+
+    /// Incrementa o X e compara ele com o limite.
+    fn incrementar_x(&mut self, limite: u64) {
+        self.set(registradores::X, self.get(registradores::X) + 1);
+
+        // O X é comparado depois de incrementado
+        comparar(self.registradores, self.get(registradores::X), limite);
+    }
+}
+
+/// Resolve o operando da instrução com os registradores atuais, seguindo o
+/// endereçamento indireto.
+fn resolver_operando(
+    registradores: &[u64],
+    memoria: &[u8],
+    instrucao: &Instrucao,
+) -> anyhow::Result<Operando> {
+    let Some(endereco) = instrucao.endereco_alvo(registradores) else {
+        return Ok(Operando::Nenhum);
+    };
+
+    Ok(match instrucao.enderecamento() {
+        Some(Enderecamento::Imediato) => Operando::Imediato(endereco),
+        Some(Enderecamento::Indireto) => Operando::Endereco(ler_palavra(memoria, endereco)?),
+        _ => Operando::Endereco(endereco),
+    })
+}
+
+/// Lê da memória, decodifica e executa uma instrução com a função indicada na definição
+/// dela no conjunto de instruções.
+///
+/// O PC é avançado para a próxima instrução antes da execução, então os desvios só
+/// precisam sobrescrever ele. Uma instrução que falha não é concluída e o PC volta para
+/// ela, com exceção do SVC, que é concluído antes de gerar a interrupção.
+///
+/// Os registradores guardam números de 24 bits em complemento de 2, então o resultado
+/// das operações aritméticas é truncado para 24 bits pelo `set_registrador`.
+///
+/// Um RD sem dados disponíveis também volta o PC, então a mesma instrução é executada
+/// de novo até o dispositivo ter dados.
+///
+/// As instruções marcadas como privilegiadas no conjunto de instruções só podem ser
+/// executadas em modo supervisor, indicado pelo bit de modo do SW.
+pub fn executar_instrucao(
+    registradores: &mut [u64],
+    memoria: &mut [u8],
+    dispositivos: &mut Dispositivos,
+    chaves: &mut [u8],
+    pendentes: &mut Vec<Interrupcao>,
+) -> anyhow::Result<()> {
+    let pc = registradores[registradores::PC];
+    let instrucao = instrucao::decodificar(memoria, pc)?;
+    if instrucao.definicao.privilegiada {
+        verificar_supervisor(registradores)?;
+    }
+
+    let operando = resolver_operando(registradores, memoria, &instrucao)?;
+    set_registrador(registradores, registradores::PC, pc + instrucao.tamanho());
+
+    let mut execucao = Execucao {
         registradores,
-        registradores::PC,
-        registradores[registradores::PC] + tamanho_instrucao,
+        memoria,
+        dispositivos,
+        chaves,
+        pendentes,
+        operando,
+        r1: instrucao.r1,
+        r2: instrucao.r2,
+        repetir: false,
+    };
+
+    let resultado = (instrucao.definicao.executar)(&mut execucao);
+    let repetir = execucao.repetir;
+
+    match resultado {
+        Err(erro) if !matches!(erro.downcast_ref(), Some(Falha::ChamadaSupervisor(_))) => {
+            set_registrador(registradores, registradores::PC, pc);
+            Err(erro)
+        }
+
+        Ok(()) if repetir => {
+            set_registrador(registradores, registradores::PC, pc);
+            Ok(())
+        }
+
+        resultado => resultado,
+    }
+}
+
+// Formato 1
+
+pub fn fix(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let f = ponto_flutuante::para_f64(execucao.get(registradores::F)).trunc();
+    if !(-0x800000 as f64..=0x7FFFFF as f64).contains(&f) {
+        return Err(Falha::Estouro.into());
+    }
+
+    execucao.set(registradores::A, f as i64 as u64);
+    Ok(())
+}
+
+pub fn float(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let a = com_sinal(execucao.get(registradores::A)) as f64;
+    execucao.set(registradores::F, ponto_flutuante::de_f64(a)?);
+    Ok(())
+}
+
+/// A conversão sempre gera números normalizados.
+pub fn norm(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let f = ponto_flutuante::para_f64(execucao.get(registradores::F));
+    execucao.set(registradores::F, ponto_flutuante::de_f64(f)?);
+    Ok(())
+}
+
+/// Canal de entrada e saída no A. Os canais não executam programas de canal, então
+/// todo SIO termina imediatamente, gerando uma interrupção de entrada e saída, e os
+/// canais estão sempre livres.
+fn canal(execucao: &Execucao) -> anyhow::Result<u8> {
+    let canal = execucao.get(registradores::A);
+    if canal > 15 {
+        return Err(anyhow!("Canal de entrada e saída inválido: {}", canal));
+    }
+
+    Ok(canal as u8)
+}
+
+pub fn sio(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let canal = canal(execucao)?;
+    execucao.pendentes.push(Interrupcao::EntradaSaida(canal));
+    Ok(())
+}
+
+pub fn hio(execucao: &mut Execucao) -> anyhow::Result<()> {
+    canal(execucao)?;
+    Ok(())
+}
+
+pub fn tio(execucao: &mut Execucao) -> anyhow::Result<()> {
+    canal(execucao)?;
+    setar_cc(execucao.registradores, Ordering::Less);
+    Ok(())
+}
+
+// Formato 2
+
+/// r2 <- (r2) + (r1)
+pub fn addr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let (r1, r2) = (execucao.r1, execucao.r2);
+    let resultado = com_sinal(execucao.registrador(r2)?) + com_sinal(execucao.registrador(r1)?);
+    execucao.set(r2 as usize, resultado as u64);
+    Ok(())
+}
+
+pub fn clear(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.registrador(execucao.r1)?;
+    execucao.set(execucao.r1 as usize, 0);
+    Ok(())
+}
+
+pub fn compr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let primeiro = execucao.registrador(execucao.r1)?;
+    let segundo = execucao.registrador(execucao.r2)?;
+    comparar(execucao.registradores, primeiro, segundo);
+    Ok(())
+}
+
+/// r2 <- (r2) / (r1)
+pub fn divr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let (r1, r2) = (execucao.r1, execucao.r2);
+    let quociente = dividir(execucao.registrador(r2)?, execucao.registrador(r1)?)?;
+    execucao.set(r2 as usize, quociente);
+    Ok(())
+}
+
+pub fn mulr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let (r1, r2) = (execucao.r1, execucao.r2);
+    let resultado = com_sinal(execucao.registrador(r2)?) * com_sinal(execucao.registrador(r1)?);
+    execucao.set(r2 as usize, resultado as u64);
+    Ok(())
+}
+
+pub fn rmo(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let valor = execucao.registrador(execucao.r1)?;
+    execucao.registrador(execucao.r2)?;
+    execucao.set(execucao.r2 as usize, valor);
+    Ok(())
+}
+
+/// O r2 guarda o número de bits menos 1. O SHIFTL é circular.
+pub fn shiftl(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let valor = execucao.registrador(execucao.r1)?;
+    let bits = execucao.r2 as u32 + 1;
+    let resultado = (valor << bits) | (valor >> (24 - bits));
+    execucao.set(execucao.r1 as usize, resultado);
+    Ok(())
+}
+
+/// O SHIFTR preenche os bits da esquerda com o bit de sinal.
+pub fn shiftr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let valor = com_sinal(execucao.registrador(execucao.r1)?);
+    execucao.set(execucao.r1 as usize, (valor >> (execucao.r2 + 1)) as u64);
+    Ok(())
+}
+
+/// r2 <- (r2) - (r1)
+pub fn subr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let (r1, r2) = (execucao.r1, execucao.r2);
+    let resultado = com_sinal(execucao.registrador(r2)?) - com_sinal(execucao.registrador(r1)?);
+    execucao.set(r2 as usize, resultado as u64);
+    Ok(())
+}
+
+pub fn tixr(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let limite = execucao.registrador(execucao.r1)?;
+    execucao.incrementar_x(limite);
+    Ok(())
+}
+
+/// A chamada vira uma interrupção de SVC, que salva o PC da próxima instrução.
+pub fn svc(execucao: &mut Execucao) -> anyhow::Result<()> {
+    Err(Falha::ChamadaSupervisor(execucao.r1).into())
+}
+
+// Formatos 3 e 4
+
+pub fn add(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let resultado = com_sinal(execucao.get(registradores::A)) + com_sinal(execucao.valor()?);
+    execucao.set(registradores::A, resultado as u64);
+    Ok(())
+}
+
+pub fn sub(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let resultado = com_sinal(execucao.get(registradores::A)) - com_sinal(execucao.valor()?);
+    execucao.set(registradores::A, resultado as u64);
+    Ok(())
+}
+
+pub fn mul(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let resultado = com_sinal(execucao.get(registradores::A)) * com_sinal(execucao.valor()?);
+    execucao.set(registradores::A, resultado as u64);
+    Ok(())
+}
+
+pub fn div(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let quociente = dividir(execucao.get(registradores::A), execucao.valor()?)?;
+    execucao.set(registradores::A, quociente);
+    Ok(())
+}
+
+pub fn and(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let resultado = execucao.get(registradores::A) & execucao.valor()?;
+    execucao.set(registradores::A, resultado);
+    Ok(())
+}
+
+pub fn or(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let resultado = execucao.get(registradores::A) | execucao.valor()?;
+    execucao.set(registradores::A, resultado);
+    Ok(())
+}
+
+pub fn comp(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let operando = execucao.valor()?;
+    comparar(
+        execucao.registradores,
+        execucao.get(registradores::A),
+        operando,
     );
+    Ok(())
+}
+
+pub fn tix(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let limite = execucao.valor()?;
+    execucao.incrementar_x(limite);
+    Ok(())
+}
+
+pub fn j(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.set(registradores::PC, execucao.destino());
+    Ok(())
+}
+
+pub fn jeq(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.desviar_se(0);
+    Ok(())
+}
+
+pub fn jgt(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.desviar_se(CC_MAIOR);
+    Ok(())
+}
+
+pub fn jlt(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.desviar_se(CC_MENOR);
+    Ok(())
+}
+
+/// O PC já aponta para a instrução depois do JSUB.
+pub fn jsub(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.set(registradores::L, execucao.get(registradores::PC));
+    execucao.set(registradores::PC, execucao.destino());
+    Ok(())
+}
+
+pub fn rsub(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.set(registradores::PC, execucao.get(registradores::L));
+    Ok(())
+}
+
+/// Carrega o operando no registrador, como o LDA e o LDX.
+pub fn carregar<const REGISTRADOR: usize>(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let valor = execucao.valor()?;
+    execucao.set(REGISTRADOR, valor);
+    Ok(())
+}
+
+/// Guarda a palavra do registrador no endereço alvo, como o STA e o STSW.
+pub fn guardar<const REGISTRADOR: usize>(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.guardar_bytes(REGISTRADOR, 3)
+}
+
+/// Somente o byte mais à direita do A é alterado.
+pub fn ldch(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let byte = execucao.byte()?;
+    let a = execucao.get(registradores::A) & 0xFFFF00;
+    execucao.set(registradores::A, a | byte);
+    Ok(())
+}
+
+pub fn stch(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.guardar_bytes(registradores::A, 1)
+}
+
+pub fn ldf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let f = ler_float(execucao.memoria, execucao.endereco()?)?;
+    execucao.set(registradores::F, f);
+    Ok(())
+}
+
+pub fn stf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.guardar_bytes(registradores::F, 6)
+}
+
+pub fn addf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.operar_float(|f, m| Ok(f + m))
+}
+
+pub fn subf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.operar_float(|f, m| Ok(f - m))
+}
+
+pub fn mulf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.operar_float(|f, m| Ok(f * m))
+}
+
+pub fn divf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    execucao.operar_float(|f, m| {
+        if m == 0.0 {
+            return Err(Falha::DivisaoPorZero.into());
+        }
+
+        Ok(f / m)
+    })
+}
+
+pub fn compf(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let f = ponto_flutuante::para_f64(execucao.get(registradores::F));
+    let operando = ponto_flutuante::para_f64(ler_float(execucao.memoria, execucao.endereco()?)?);
+
+    // Os números são sempre finitos, então a comparação sempre existe
+    let ordem = f.partial_cmp(&operando).unwrap_or(Ordering::Equal);
+    setar_cc(execucao.registradores, ordem);
+    Ok(())
+}
+
+/// Pronto é CC <, ocupado é CC =. O número do dispositivo é o byte do operando.
+pub fn td(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let numero = execucao.byte()? as u8;
+    let ordem = if execucao.dispositivos.obter(numero).testar() {
+        Ordering::Less
+    } else {
+        Ordering::Equal
+    };
+
+    setar_cc(execucao.registradores, ordem);
+    Ok(())
+}
+
+pub fn rd(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let numero = execucao.byte()? as u8;
+    let Some(byte) = execucao.dispositivos.obter(numero).ler()? else {
+        execucao.repetir = true;
+        return Ok(());
+    };
+
+    let a = execucao.get(registradores::A) & 0xFFFF00;
+    execucao.set(registradores::A, a | byte as u64);
+    Ok(())
+}
+
+pub fn wd(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let numero = execucao.byte()? as u8;
+    let a = execucao.get(registradores::A) as u8;
+    execucao.dispositivos.obter(numero).escrever(a)
+}
+
+pub fn lps(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let endereco = execucao.endereco()?;
+    interrupcoes::carregar_status(execucao.registradores, execucao.memoria, endereco)
+}
+
+pub fn sti(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let valor = execucao.valor()?;
+    execucao.set(registradores::I, valor);
+    Ok(())
+}
+
+/// A chave do bloco do endereço alvo recebe os 4 bits menos significativos do A.
+pub fn ssk(execucao: &mut Execucao) -> anyhow::Result<()> {
+    let endereco = execucao.endereco()?;
+    let a = execucao.get(registradores::A);
+    let chave = execucao
+        .chaves
+        .get_mut(endereco as usize / TAMANHO_BLOCO)
+        .context("Endereço de memória inválido")?;
 
+    *chave = (a & 0x0F) as u8;
     Ok(())
 }
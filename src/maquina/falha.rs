@@ -0,0 +1,34 @@
+use std::fmt;
+
+/// Falha da máquina durante a execução de uma instrução. É retornada dentro do
+/// `anyhow::Error`, de onde pode ser recuperada com `downcast_ref`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Falha {
+    InstrucaoInvalida,
+    DivisaoPorZero,
+    /// Resultado grande demais para o registrador
+    Estouro,
+    /// Instrução privilegiada executada em modo usuário
+    InstrucaoPrivilegiada,
+    /// Escrita em um bloco da memória com uma chave de proteção diferente da do SW
+    ProtecaoMemoria,
+    /// SVC executado, com o número da chamada
+    ChamadaSupervisor(u8),
+}
+
+impl fmt::Display for Falha {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Falha::InstrucaoInvalida => write!(f, "Falha: instrução inválida"),
+            Falha::DivisaoPorZero => write!(f, "Falha: divisão por zero"),
+            Falha::Estouro => write!(f, "Falha: estouro aritmético"),
+            Falha::InstrucaoPrivilegiada => {
+                write!(f, "Falha: instrução privilegiada em modo usuário")
+            }
+            Falha::ProtecaoMemoria => write!(f, "Falha: violação de proteção da memória"),
+            Falha::ChamadaSupervisor(numero) => write!(f, "Chamada ao supervisor {}", numero),
+        }
+    }
+}
+
+impl std::error::Error for Falha {}
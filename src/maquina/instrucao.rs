@@ -0,0 +1,190 @@
+//! Decodificação das instruções da SIC/XE, usada pelo executor e por quem precisa
+//! mostrar as instruções da memória.
+
+use crate::maquina::conjunto_instrucoes::{self, DefinicaoInstrucao};
+use crate::maquina::constantes::registradores;
+use crate::maquina::falha::Falha;
+use anyhow::{Context, anyhow};
+use bitreader::BitReader;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Formato {
+    /// Somente o opcode
+    Um,
+    /// Opcode e dois registradores
+    Dois,
+    /// Deslocamento de 12 bits, ou endereço de 15 bits na SIC padrão
+    Tres,
+    /// Endereço de 20 bits
+    Quatro,
+}
+
+/// Flags das instruções de formato 3 e 4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Flags {
+    pub n: bool,
+    pub i: bool,
+    pub x: bool,
+    pub b: bool,
+    pub p: bool,
+    pub e: bool,
+}
+
+/// Modo de endereçamento, indicado pelas flags n e i.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Enderecamento {
+    /// O operando é o próprio endereço alvo
+    Imediato,
+    /// O endereço alvo contém o endereço do operando
+    Indireto,
+    /// O operando está no endereço alvo, inclusive na SIC padrão
+    Simples,
+}
+
+/// Instrução decodificada da memória.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Instrucao {
+    /// Endereço de onde a instrução foi lida
+    pub endereco: u64,
+    /// Definição da instrução na tabela do conjunto de instruções
+    pub definicao: &'static DefinicaoInstrucao,
+    pub formato: Formato,
+    /// Opcode sem os bits n e i
+    pub opcode: u8,
+    pub flags: Flags,
+    /// Registradores do formato 2. O SVC usa o r1 como número da chamada e o SHIFTL e o
+    /// SHIFTR usam o r2 como número de bits
+    pub r1: u8,
+    pub r2: u8,
+    /// Deslocamento do formato 3, endereço do formato 4 ou da SIC padrão
+    pub deslocamento: u64,
+}
+
+/// Decodifica a instrução no endereço da memória, sem executar nada. Opcodes que não
+/// estão no conjunto de instruções são uma `Falha::InstrucaoInvalida` e combinações de
+/// flags que não existem na SIC/XE são um erro.
+pub fn decodificar(memoria: &[u8], endereco: u64) -> anyhow::Result<Instrucao> {
+    let Some(proximas) = memoria
+        .get(endereco as usize..)
+        .filter(|proximas| !proximas.is_empty())
+    else {
+        return Err(anyhow!("Endereço {:06X} fora da memória", endereco));
+    };
+
+    let mut bits = BitReader::new(proximas);
+    let opcode = bits.read_u8(8).context("Erro ao ler instrução")?;
+    let Some(definicao) = conjunto_instrucoes::por_opcode(opcode) else {
+        return Err(Falha::InstrucaoInvalida.into());
+    };
+
+    let mut instrucao = Instrucao {
+        endereco,
+        definicao,
+        formato: definicao.formato,
+        opcode,
+        flags: Flags::default(),
+        r1: 0,
+        r2: 0,
+        deslocamento: 0,
+    };
+
+    match instrucao.formato {
+        Formato::Um => {}
+        Formato::Dois => {
+            instrucao.r1 = bits.read_u8(4).context("Erro ao ler instrução")?;
+            instrucao.r2 = bits.read_u8(4).context("Erro ao ler instrução")?;
+        }
+
+        _ => {
+            // Primeiros 6 bits
+            instrucao.opcode = opcode & 0xFC;
+
+            let flags = &mut instrucao.flags;
+            flags.n = opcode & 0x02 != 0;
+            flags.i = opcode & 0x01 != 0;
+            flags.x = bits.read_bool().context("Erro ao ler flags da instrução")?;
+
+            // Direto formato SIC, com 15 bits de endereço e somente a flag x
+            if !flags.n && !flags.i {
+                instrucao.deslocamento = bits
+                    .read_u64(15)
+                    .context("Erro ao ler endereço da instrução")?;
+
+                return Ok(instrucao);
+            }
+
+            flags.b = bits.read_bool().context("Erro ao ler flags da instrução")?;
+            flags.p = bits.read_bool().context("Erro ao ler flags da instrução")?;
+            flags.e = bits.read_bool().context("Erro ao ler flags da instrução")?;
+
+            // O formato 4 não é relativo, a indexação só existe no endereçamento simples
+            if (flags.b && flags.p)
+                || (flags.e && (flags.b || flags.p))
+                || (flags.x && !(flags.n && flags.i))
+            {
+                return Err(anyhow!("Modo de endereçamento inválido"));
+            }
+
+            if flags.e {
+                instrucao.formato = Formato::Quatro;
+            }
+
+            let tamanho = if flags.e { 20 } else { 12 };
+            instrucao.deslocamento = bits
+                .read_u64(tamanho)
+                .context("Erro ao ler endereço da instrução")?;
+        }
+    }
+
+    Ok(instrucao)
+}
+
+impl Instrucao {
+    pub fn tamanho(&self) -> u64 {
+        match self.formato {
+            Formato::Um => 1,
+            Formato::Dois => 2,
+            Formato::Tres => 3,
+            Formato::Quatro => 4,
+        }
+    }
+
+    /// Modo de endereçamento das instruções de formato 3 e 4.
+    pub fn enderecamento(&self) -> Option<Enderecamento> {
+        match (self.formato, self.flags.n, self.flags.i) {
+            (Formato::Um | Formato::Dois, _, _) => None,
+            (_, false, true) => Some(Enderecamento::Imediato),
+            (_, true, false) => Some(Enderecamento::Indireto),
+            _ => Some(Enderecamento::Simples),
+        }
+    }
+
+    /// Deslocamento relativo ao PC, que tem sinal e parte da próxima instrução.
+    pub fn deslocamento_com_sinal(&self) -> i64 {
+        if self.flags.p && self.deslocamento & 0x800 != 0 {
+            self.deslocamento as i64 - 0x1000
+        } else {
+            self.deslocamento as i64
+        }
+    }
+
+    /// Endereço alvo das instruções de formato 3 e 4, antes de seguir o endereçamento
+    /// indireto. Os registradores são usados no endereçamento relativo à base e na
+    /// indexação.
+    pub fn endereco_alvo(&self, registradores: &[u64]) -> Option<u64> {
+        self.enderecamento()?;
+
+        let mut endereco = self.deslocamento_com_sinal();
+        if self.flags.p {
+            endereco += (self.endereco + self.tamanho()) as i64;
+        } else if self.flags.b {
+            endereco += registradores[registradores::B] as i64;
+        }
+
+        if self.flags.x {
+            endereco += registradores[registradores::X] as i64;
+        }
+
+        Some(endereco as u64 & 0xFFFFFF)
+    }
+}
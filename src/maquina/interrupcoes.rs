@@ -0,0 +1,160 @@
+//! Interrupções da SIC/XE.
+//!
+//! Cada classe de interrupção tem uma área de trabalho fixa na memória:
+//!
+//! ```text
+//! 00  novo SW
+//! 03  novo PC
+//! 06  status salvo: SW, PC, A, X, L, B, S e T com 3 bytes cada e F com 6 bytes
+//! ```
+//!
+//! Quando a interrupção acontece o status atual é salvo na área de trabalho e o SW e o
+//! PC novos são carregados, com o código da interrupção no ICODE do SW. A rotina de
+//! tratamento retorna com um LPS no status salvo.
+
+use crate::maquina::constantes::{registradores, status};
+use crate::maquina::executor::{escrever_palavra, ler_float, ler_palavra, set_registrador};
+use crate::maquina::falha::Falha;
+use anyhow::Context;
+
+/// Códigos das interrupções de programa.
+pub const CODIGO_INSTRUCAO_INVALIDA: u8 = 0x00;
+pub const CODIGO_INSTRUCAO_PRIVILEGIADA: u8 = 0x01;
+pub const CODIGO_PROTECAO_MEMORIA: u8 = 0x03;
+pub const CODIGO_ESTOURO: u8 = 0x04;
+
+/// Posição do status salvo dentro da área de trabalho.
+pub const DESLOCAMENTO_STATUS: u64 = 6;
+
+/// Ordem dos registradores de 3 bytes no status salvo. O F vem depois deles, com 6 bytes.
+const ORDEM_STATUS: [usize; 8] = [
+    registradores::SW,
+    registradores::PC,
+    registradores::A,
+    registradores::X,
+    registradores::L,
+    registradores::B,
+    registradores::S,
+    registradores::T,
+];
+
+/// Tamanho em bytes do status salvo.
+const TAMANHO_STATUS: usize = 30;
+
+/// Interrupções, da classe de maior prioridade para a de menor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupcao {
+    /// Classe I, gerada pelo SVC com o número da chamada
+    Svc(u8),
+    /// Classe II, gerada por uma falha na execução de uma instrução, com o código dela
+    Programa(u8),
+    /// Classe III, gerada quando o temporizador de intervalo chega a zero
+    Temporizador,
+    /// Classe IV, gerada quando um canal termina, com o número do canal
+    EntradaSaida(u8),
+}
+
+impl Interrupcao {
+    /// Número da classe, a classe 1 tem a maior prioridade.
+    pub fn classe(&self) -> u8 {
+        match self {
+            Interrupcao::Svc(_) => 1,
+            Interrupcao::Programa(_) => 2,
+            Interrupcao::Temporizador => 3,
+            Interrupcao::EntradaSaida(_) => 4,
+        }
+    }
+
+    pub fn area_trabalho(&self) -> u64 {
+        match self {
+            Interrupcao::Svc(_) => 0x100,
+            Interrupcao::Programa(_) => 0x130,
+            Interrupcao::Temporizador => 0x160,
+            Interrupcao::EntradaSaida(_) => 0x190,
+        }
+    }
+
+    /// Código colocado no ICODE do SW.
+    pub fn codigo(&self) -> u8 {
+        match self {
+            Interrupcao::Svc(codigo)
+            | Interrupcao::Programa(codigo)
+            | Interrupcao::EntradaSaida(codigo) => *codigo,
+            Interrupcao::Temporizador => 0,
+        }
+    }
+
+    /// Retorna se a interrupção é permitida pela máscara do SW.
+    pub fn permitida(&self, sw: u64) -> bool {
+        match self {
+            Interrupcao::Svc(_) | Interrupcao::Programa(_) => true,
+            Interrupcao::Temporizador => sw & status::MASCARA_TEMPORIZADOR != 0,
+            Interrupcao::EntradaSaida(_) => sw & status::MASCARA_ENTRADA_SAIDA != 0,
+        }
+    }
+}
+
+impl From<Falha> for Interrupcao {
+    fn from(falha: Falha) -> Self {
+        match falha {
+            Falha::InstrucaoInvalida => Interrupcao::Programa(CODIGO_INSTRUCAO_INVALIDA),
+            Falha::InstrucaoPrivilegiada => Interrupcao::Programa(CODIGO_INSTRUCAO_PRIVILEGIADA),
+            Falha::ProtecaoMemoria => Interrupcao::Programa(CODIGO_PROTECAO_MEMORIA),
+            Falha::DivisaoPorZero | Falha::Estouro => Interrupcao::Programa(CODIGO_ESTOURO),
+            Falha::ChamadaSupervisor(numero) => Interrupcao::Svc(numero),
+        }
+    }
+}
+
+/// Salva o status atual na área de trabalho da interrupção e carrega o SW e o PC da
+/// rotina de tratamento.
+pub fn interromper(
+    registradores: &mut [u64],
+    memoria: &mut [u8],
+    interrupcao: Interrupcao,
+) -> anyhow::Result<()> {
+    let area = interrupcao.area_trabalho();
+    let sw = ler_palavra(memoria, area)?;
+    let pc = ler_palavra(memoria, area + 3)?;
+    salvar_status(registradores, memoria, area + DESLOCAMENTO_STATUS)?;
+
+    let sw = (sw & !status::MASCARA_ICODE) | interrupcao.codigo() as u64;
+    set_registrador(registradores, registradores::SW, sw);
+    set_registrador(registradores, registradores::PC, pc);
+    Ok(())
+}
+
+/// Salva os registradores a partir do endereço.
+fn salvar_status(registradores: &[u64], memoria: &mut [u8], endereco: u64) -> anyhow::Result<()> {
+    let status = memoria
+        .get_mut(endereco as usize..endereco as usize + TAMANHO_STATUS)
+        .context("Endereço de memória inválido")?;
+
+    for (indice, registrador) in ORDEM_STATUS.iter().enumerate() {
+        escrever_palavra(status, indice as u64 * 3, registradores[*registrador])?;
+    }
+
+    let f = registradores[registradores::F].to_be_bytes();
+    status[ORDEM_STATUS.len() * 3..].copy_from_slice(&f[2..]);
+    Ok(())
+}
+
+/// Carrega os registradores do status salvo a partir do endereço, usado pelo LPS.
+pub fn carregar_status(
+    registradores: &mut [u64],
+    memoria: &[u8],
+    endereco: u64,
+) -> anyhow::Result<()> {
+    let status = memoria
+        .get(endereco as usize..endereco as usize + TAMANHO_STATUS)
+        .context("Endereço de memória inválido")?;
+
+    for (indice, registrador) in ORDEM_STATUS.iter().enumerate() {
+        let valor = ler_palavra(status, indice as u64 * 3)?;
+        set_registrador(registradores, *registrador, valor);
+    }
+
+    let f = ler_float(status, ORDEM_STATUS.len() as u64 * 3)?;
+    set_registrador(registradores, registradores::F, f);
+    Ok(())
+}
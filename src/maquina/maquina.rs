@@ -1,61 +1,432 @@
-use crate::maquina::constantes::registradores;
+use crate::maquina::carregador;
+use crate::maquina::constantes::{registradores, status};
+use crate::maquina::dispositivos::{Dispositivo, Dispositivos};
 use crate::maquina::executor;
-use anyhow::Context;
+use crate::maquina::falha::Falha;
+use crate::maquina::instrucao;
+use crate::maquina::interrupcoes::{self, Interrupcao};
+use anyhow::anyhow;
+use std::collections::{BTreeSet, HashMap};
+
+/// Endereço padrão onde os programas são carregados.
+pub const ENDERECO_CARGA: usize = 0x6000;
+
+/// Tamanho da memória da SIC padrão, também o tamanho mínimo.
+pub const TAMANHO_MEMORIA_SIC: usize = 32768;
+
+/// Tamanho máximo da memória, todo o espaço de endereçamento de 20 bits do formato 4.
+pub const TAMANHO_MEMORIA_MAXIMO: usize = 1 << 20;
+
+/// Motivo pelo qual `Maquina::executar_ate_parar` parou.
+#[derive(Debug)]
+pub enum MotivoParada {
+    /// O PC chegou num ponto de parada, a instrução dele ainda não foi executada
+    PontoParada(usize),
+    /// O PC saiu do programa carregado ou a instrução é um desvio para ela mesma, como
+    /// o `FIM J FIM` no final dos programas
+    Fim,
+    /// Erro ao executar uma instrução, como as falhas com as interrupções desabilitadas
+    Erro(anyhow::Error),
+    /// Todas as instruções permitidas foram executadas sem parar
+    LimitePassos,
+}
+
+/// Configuração de uma nova máquina, criado por `Maquina::construtor`.
+pub struct ConstrutorMaquina {
+    tamanho_memoria: usize,
+    endereco_carga: usize,
+}
+
+impl Default for ConstrutorMaquina {
+    fn default() -> Self {
+        Self {
+            tamanho_memoria: TAMANHO_MEMORIA_SIC,
+            endereco_carga: ENDERECO_CARGA,
+        }
+    }
+}
+
+impl ConstrutorMaquina {
+    /// Tamanho da memória em bytes, múltiplo de 2048 entre 32 KB e 1 MB.
+    pub fn tamanho_memoria(mut self, tamanho: usize) -> Self {
+        self.tamanho_memoria = tamanho;
+        self
+    }
+
+    /// Endereço onde os programas são carregados por padrão.
+    pub fn endereco_carga(mut self, endereco: usize) -> Self {
+        self.endereco_carga = endereco;
+        self
+    }
+
+    pub fn construir(self) -> anyhow::Result<Maquina> {
+        if !(TAMANHO_MEMORIA_SIC..=TAMANHO_MEMORIA_MAXIMO).contains(&self.tamanho_memoria)
+            || !self.tamanho_memoria.is_multiple_of(executor::TAMANHO_BLOCO)
+        {
+            return Err(anyhow!(
+                "Tamanho de memória inválido: {}, deve ser um múltiplo de {} entre {} e {}",
+                self.tamanho_memoria,
+                executor::TAMANHO_BLOCO,
+                TAMANHO_MEMORIA_SIC,
+                TAMANHO_MEMORIA_MAXIMO
+            ));
+        }
+
+        if self.endereco_carga >= self.tamanho_memoria {
+            return Err(anyhow!(
+                "Endereço de carga {:06X} fora da memória",
+                self.endereco_carga
+            ));
+        }
+
+        let mut registradores = [0; 10];
+        registradores[registradores::SW] = status::MODO_SUPERVISOR;
+
+        Ok(Maquina {
+            registradores,
+            memoria: vec![0; self.tamanho_memoria],
+            tamanho_programa_atual: 0,
+            endereco_carga_padrao: self.endereco_carga,
+            endereco_carga: self.endereco_carga,
+            endereco_execucao: self.endereco_carga,
+            dispositivos: Dispositivos::default(),
+            chaves: vec![0; self.tamanho_memoria / executor::TAMANHO_BLOCO],
+            interrupcoes: false,
+            pendentes: Vec::new(),
+            simbolos: HashMap::new(),
+            pontos_parada: BTreeSet::new(),
+            retomar_de: None,
+        })
+    }
+}
 
 /// Representa uma máquina SIC/XE.
 pub struct Maquina {
     registradores: [u64; 10],
-    memoria: [u8; 32768],
+    memoria: Vec<u8>,
     tamanho_programa_atual: usize,
+    endereco_carga_padrao: usize,
+    /// Endereço onde o programa atual foi carregado
+    endereco_carga: usize,
+    endereco_execucao: usize,
+    dispositivos: Dispositivos,
+    /// Chave de proteção de cada bloco da memória
+    chaves: Vec<u8>,
+    /// Com as interrupções desabilitadas as falhas são retornadas por `executar_instrucao`
+    interrupcoes: bool,
+    /// Interrupções de temporizador e de entrada e saída esperando a máscara permitir
+    pendentes: Vec<Interrupcao>,
+    /// Endereços dos símbolos do programa carregado
+    simbolos: HashMap<String, usize>,
+    /// Endereços onde `executar_ate_parar` para antes de executar a instrução
+    pontos_parada: BTreeSet<usize>,
+    /// Ponto de parada onde a execução parou, que é ignorado até a instrução dele executar
+    retomar_de: Option<usize>,
 }
 
 impl Maquina {
+    /// Cria uma SIC/XE com 32 KB de memória que carrega os programas em 0x6000.
     pub fn new() -> Self {
-        Self {
-            registradores: [0; 10],
-            memoria: [0; 32768],
-            tamanho_programa_atual: 0,
-        }
+        Self::construtor()
+            .construir()
+            .expect("A configuração padrão é válida")
+    }
+
+    pub fn construtor() -> ConstrutorMaquina {
+        ConstrutorMaquina::default()
+    }
+
+    /// Carrega um programa objeto no endereço de carga padrão da memória. Programas com
+    /// várias seções de controle são ligados entre si.
+    pub fn carregar_objeto(&mut self, objeto: &str) -> anyhow::Result<()> {
+        self.ligar_objetos(&[objeto], self.endereco_carga_padrao)
     }
 
-    /// Carrega um programa no endereço 0x6000 da memória.
-    pub fn carregar(&mut self, programa: &[u8]) -> anyhow::Result<()> {
-        self.memoria[0x6000..].copy_from_slice(&[0; 0x2000]);
-        self.memoria
-            .get_mut(0x6000..0x6000 + programa.len())
-            .context("Programa possui tamanho maior que o possível de carregar")?
-            .copy_from_slice(programa);
+    /// Liga e carrega vários programas objeto em sequência a partir de `endereco_programa`,
+    /// resolvendo as referências externas entre eles. A execução começa no endereço do
+    /// primeiro registro E que indicar um.
+    pub fn ligar_objetos(
+        &mut self,
+        objetos: &[&str],
+        endereco_programa: usize,
+    ) -> anyhow::Result<()> {
+        let mut programas = Vec::new();
+        for objeto in objetos {
+            programas.extend(carregador::ler_programas(objeto)?);
+        }
+
+        if programas.is_empty() {
+            return Err(anyhow!("Nenhum programa objeto para carregar"));
+        }
+
+        // A memória só é alterada se a ligação funcionar
+        let mut memoria = self.memoria.clone();
+        let inicio = endereco_programa.min(memoria.len());
+        memoria[inicio..].fill(0);
+        let ligacao = carregador::ligar(&mut memoria, &programas, endereco_programa)?;
+
+        self.memoria = memoria;
+        self.endereco_carga = endereco_programa;
+        self.endereco_execucao = ligacao.endereco_execucao;
+        self.tamanho_programa_atual = ligacao.tamanho;
+        self.simbolos = ligacao.simbolos_externos;
+        self.retomar_de = None;
+
+        executor::set_registrador(
+            &mut self.registradores,
+            registradores::PC,
+            self.endereco_execucao as u64,
+        );
 
-        executor::set_registrador(&mut self.registradores, registradores::PC, 0x6000);
-        self.tamanho_programa_atual = programa.len();
         Ok(())
     }
 
+    /// Adiciona símbolos do programa carregado, com os endereços relativos ao endereço
+    /// de carga, como os labels gerados pelo montador. Os nomes das seções e os
+    /// símbolos externos já são adicionados pela ligação.
+    pub fn adicionar_simbolos(&mut self, simbolos: &[(String, usize)]) {
+        for (nome, endereco) in simbolos {
+            self.simbolos
+                .insert(nome.clone(), self.endereco_carga + endereco);
+        }
+    }
+
+    /// Símbolos do programa carregado e seus endereços.
+    pub fn simbolos(&self) -> &HashMap<String, usize> {
+        &self.simbolos
+    }
+
+    /// Adiciona um ponto de parada no endereço.
+    pub fn adicionar_ponto_parada(&mut self, endereco: usize) {
+        self.pontos_parada.insert(endereco);
+    }
+
+    /// Adiciona um ponto de parada no endereço de um símbolo do programa carregado,
+    /// retornando o endereço.
+    pub fn adicionar_ponto_parada_simbolo(&mut self, simbolo: &str) -> anyhow::Result<usize> {
+        let Some(endereco) = self.simbolos.get(simbolo).copied() else {
+            return Err(anyhow!("Símbolo não encontrado: {}", simbolo));
+        };
+
+        self.pontos_parada.insert(endereco);
+        Ok(endereco)
+    }
+
+    pub fn remover_ponto_parada(&mut self, endereco: usize) {
+        self.pontos_parada.remove(&endereco);
+    }
+
+    /// Adiciona o ponto de parada caso ele não exista, ou remove caso exista. Retorna
+    /// se o ponto de parada existe depois da alteração.
+    pub fn alternar_ponto_parada(&mut self, endereco: usize) -> bool {
+        if self.pontos_parada.remove(&endereco) {
+            return false;
+        }
+
+        self.pontos_parada.insert(endereco);
+        true
+    }
+
+    /// Endereços dos pontos de parada, em ordem.
+    pub fn pontos_parada(&self) -> &BTreeSet<usize> {
+        &self.pontos_parada
+    }
+
+    /// Conecta um dispositivo de entrada e saída no número informado.
+    pub fn conectar_dispositivo(&mut self, numero: u8, dispositivo: Box<dyn Dispositivo>) {
+        self.dispositivos.conectar(numero, dispositivo);
+    }
+
+    /// Desconecta o dispositivo do número informado, que passa a usar o arquivo dele.
+    pub fn desconectar_dispositivo(&mut self, numero: u8) {
+        self.dispositivos.desconectar(numero);
+    }
+
+    /// Habilita ou desabilita as interrupções. Habilitadas, as falhas e os SVC viram
+    /// interrupções tratadas pelo programa carregado, que deve preencher as áreas de
+    /// trabalho em 0x100, 0x130, 0x160 e 0x190.
+    pub fn habilitar_interrupcoes(&mut self, habilitar: bool) {
+        self.interrupcoes = habilitar;
+        self.pendentes.clear();
+    }
+
+    pub fn interrupcoes_habilitadas(&self) -> bool {
+        self.interrupcoes
+    }
+
     /// Retorna o valor de um registrador caso o número seja válido.
     pub fn registrador(&self, numero: usize) -> Option<u64> {
         self.registradores.get(numero).copied()
     }
 
+    /// Retorna todos os registradores, indexados pelos números de `registradores`.
+    pub fn registradores(&self) -> &[u64] {
+        &self.registradores
+    }
+
+    /// Endereço onde os programas são carregados por padrão.
+    pub fn endereco_carga_padrao(&self) -> usize {
+        self.endereco_carga_padrao
+    }
+
+    /// Endereço onde o programa atual foi carregado.
+    pub fn endereco_carga(&self) -> usize {
+        self.endereco_carga
+    }
+
+    /// Tamanho do programa atual, que ocupa a memória a partir do endereço de carga.
+    pub fn tamanho_programa(&self) -> usize {
+        self.tamanho_programa_atual
+    }
+
     /// Retorna um slice da memória.
     pub fn memoria(&self) -> &[u8] {
         &self.memoria
     }
 
     /// Lê da memória, decodifica e executa uma instrução.
+    ///
+    /// Com as interrupções habilitadas, uma interrupção pendente permitida pela máscara
+    /// do SW é tratada no lugar da instrução.
     pub fn executar_instrucao(&mut self) -> anyhow::Result<()> {
-        if self.tamanho_programa_atual == 0
-            || self.registradores[registradores::PC] - 0x6000 >= self.tamanho_programa_atual as u64
-        {
-            Err(anyhow::anyhow!("Execução finalizada"))
-        } else {
-            executor::executar_instrucao(&mut self.registradores, &mut self.memoria)
+        if let Some(interrupcao) = self.proxima_interrupcao() {
+            return interrupcoes::interromper(
+                &mut self.registradores,
+                &mut self.memoria,
+                interrupcao,
+            );
+        }
+
+        if !self.pc_no_programa() {
+            return Err(anyhow::anyhow!("Execução finalizada"));
         }
+
+        // O tempo é contado antes, para o STI não decrementar o valor que acabou de setar
+        self.contar_tempo();
+        let resultado = executor::executar_instrucao(
+            &mut self.registradores,
+            &mut self.memoria,
+            &mut self.dispositivos,
+            &mut self.chaves,
+            &mut self.pendentes,
+        );
+
+        if !self.interrupcoes {
+            self.pendentes.clear();
+            return resultado;
+        }
+
+        match resultado {
+            Err(erro) => match erro.downcast_ref::<Falha>() {
+                Some(falha) => interrupcoes::interromper(
+                    &mut self.registradores,
+                    &mut self.memoria,
+                    Interrupcao::from(*falha),
+                ),
+                None => Err(erro),
+            },
+            Ok(()) => Ok(()),
+        }
+    }
+
+    /// Executa instruções até chegar num ponto de parada, terminar o programa, acontecer
+    /// um erro ou executar `limite` instruções. Depois de parar num ponto de parada, a
+    /// próxima chamada executa a instrução dele para continuar a execução.
+    pub fn executar_ate_parar(&mut self, limite: usize) -> MotivoParada {
+        for _ in 0..limite {
+            let pc = self.registradores[registradores::PC] as usize;
+            // O PC também pode mudar fora daqui, como no passo a passo
+            let retomando = self.retomar_de.take() == Some(pc);
+            if !retomando && self.pontos_parada.contains(&pc) {
+                self.retomar_de = Some(pc);
+                return MotivoParada::PontoParada(pc);
+            }
+
+            // Uma interrupção pendente ainda pode desviar para a rotina de tratamento
+            let sw = self.registradores[registradores::SW];
+            let interrompida = self
+                .pendentes
+                .iter()
+                .any(|interrupcao| interrupcao.permitida(sw));
+
+            if !self.pc_no_programa() && !interrompida {
+                return MotivoParada::Fim;
+            }
+
+            if let Err(erro) = self.executar_instrucao() {
+                return MotivoParada::Erro(erro);
+            }
+
+            // O RD esperando dados não sai do ponto de parada
+            if retomando && self.registradores[registradores::PC] as usize == pc {
+                self.retomar_de = Some(pc);
+            }
+
+            // O RD esperando dados também não altera o PC, mas não é o fim
+            let desvio = instrucao::decodificar(&self.memoria, pc as u64)
+                .is_ok_and(|instrucao| instrucao.definicao.mnemonico == "J");
+
+            if !interrompida && desvio && self.registradores[registradores::PC] as usize == pc {
+                return MotivoParada::Fim;
+            }
+        }
+
+        MotivoParada::LimitePassos
+    }
+
+    /// Retorna se o PC está dentro do programa carregado.
+    fn pc_no_programa(&self) -> bool {
+        let pc = self.registradores[registradores::PC] as usize;
+        self.tamanho_programa_atual != 0
+            && (self.endereco_carga..self.endereco_carga + self.tamanho_programa_atual)
+                .contains(&pc)
+    }
+
+    /// Retira a interrupção pendente de maior prioridade permitida pela máscara do SW.
+    fn proxima_interrupcao(&mut self) -> Option<Interrupcao> {
+        let sw = self.registradores[registradores::SW];
+        let (indice, _) = self
+            .pendentes
+            .iter()
+            .enumerate()
+            .filter(|(_, interrupcao)| interrupcao.permitida(sw))
+            .min_by_key(|(_, interrupcao)| interrupcao.classe())?;
+
+        Some(self.pendentes.remove(indice))
+    }
+
+    /// Decrementa o temporizador de intervalo, que gera uma interrupção ao chegar a zero.
+    fn contar_tempo(&mut self) {
+        let i = self.registradores[registradores::I];
+        if i == 0 {
+            return;
+        }
+
+        self.registradores[registradores::I] = i - 1;
+        if i == 1 && self.interrupcoes {
+            self.pendentes.push(Interrupcao::Temporizador);
+        }
+    }
+
+    /// Retorna se a máquina está em modo supervisor, no qual as instruções privilegiadas
+    /// podem ser executadas. A máquina começa em modo supervisor e o modo só muda quando
+    /// o SW é carregado pelo LPS.
+    pub fn modo_supervisor(&self) -> bool {
+        self.registradores[registradores::SW] & status::MODO_SUPERVISOR != 0
     }
 
     /// Reseta a máquina sem remover o programa carregado
     pub fn resetar(&mut self) {
-        self.memoria[..0x6000].copy_from_slice(&[0; 0x6000]);
+        self.memoria[..self.endereco_carga].fill(0);
+        self.chaves.fill(0);
+        self.pendentes.clear();
+        self.retomar_de = None;
         self.registradores = [0; 10];
-        executor::set_registrador(&mut self.registradores, registradores::PC, 0x6000);
+        self.registradores[registradores::SW] = status::MODO_SUPERVISOR;
+        executor::set_registrador(
+            &mut self.registradores,
+            registradores::PC,
+            self.endereco_execucao as u64,
+        );
     }
 }
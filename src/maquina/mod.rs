@@ -1,6 +1,14 @@
+pub mod carregador;
+pub mod conjunto_instrucoes;
 pub mod constantes;
+pub mod desmontador;
+pub mod dispositivos;
 mod executor;
+pub mod falha;
+pub mod instrucao;
+pub mod interrupcoes;
 #[allow(clippy::module_inception)]
 pub mod maquina;
+pub mod ponto_flutuante;
 #[cfg(test)]
 mod tests;
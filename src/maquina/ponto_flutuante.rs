@@ -0,0 +1,63 @@
+//! Formato de ponto flutuante de 48 bits do SIC/XE: 1 bit de sinal, 11 bits de expoente
+//! e 36 bits de fração. O valor é fração * 2^(expoente - 1024), com a fração entre 0,5 e 1
+//! quando normalizada. O zero tem todos os bits zerados.
+
+use crate::maquina::falha::Falha;
+
+const BITS_FRACAO: u32 = 36;
+const MASCARA_FRACAO: u64 = (1 << BITS_FRACAO) - 1;
+const MASCARA_EXPOENTE: u64 = 0x7FF;
+const BIT_SINAL: u64 = 1 << 47;
+const DESLOCAMENTO_EXPOENTE: i32 = 1024;
+
+/// Converte um número no formato do SIC/XE para f64, sem perda de precisão.
+pub fn para_f64(valor: u64) -> f64 {
+    let fracao = (valor & MASCARA_FRACAO) as f64 / (1u64 << BITS_FRACAO) as f64;
+    let expoente = ((valor >> BITS_FRACAO) & MASCARA_EXPOENTE) as i32 - DESLOCAMENTO_EXPOENTE;
+    let numero = fracao * 2f64.powi(expoente);
+
+    if valor & BIT_SINAL != 0 {
+        -numero
+    } else {
+        numero
+    }
+}
+
+/// Converte um f64 para o formato do SIC/XE, normalizado e com a fração truncada.
+/// Números pequenos demais viram zero e números grandes demais são um estouro.
+pub fn de_f64(numero: f64) -> anyhow::Result<u64> {
+    if !numero.is_finite() {
+        return Err(Falha::Estouro.into());
+    }
+
+    if numero == 0.0 {
+        return Ok(0);
+    }
+
+    // Multiplicar e dividir por 2 é exato, então a fração não perde precisão
+    let mut fracao = numero.abs();
+    let mut expoente = 0;
+    while fracao >= 1.0 {
+        fracao /= 2.0;
+        expoente += 1;
+    }
+
+    while fracao < 0.5 {
+        fracao *= 2.0;
+        expoente -= 1;
+    }
+
+    let expoente = expoente + DESLOCAMENTO_EXPOENTE;
+    if expoente < 0 {
+        return Ok(0);
+    }
+
+    if expoente > MASCARA_EXPOENTE as i32 {
+        return Err(Falha::Estouro.into());
+    }
+
+    let fracao = (fracao * (1u64 << BITS_FRACAO) as f64) as u64 & MASCARA_FRACAO;
+    let sinal = if numero < 0.0 { BIT_SINAL } else { 0 };
+
+    Ok(sinal | ((expoente as u64) << BITS_FRACAO) | fracao)
+}
@@ -1,10 +1,34 @@
 use crate::maquina::constantes::registradores;
-use crate::maquina::maquina::Maquina;
+use crate::maquina::desmontador::{self, LinhaDesmontada};
+use crate::maquina::dispositivos::Fila;
+use crate::maquina::falha::Falha;
+use crate::maquina::instrucao::{self, Enderecamento, Formato};
+use crate::maquina::interrupcoes;
+use crate::maquina::maquina::{Maquina, MotivoParada, TAMANHO_MEMORIA_MAXIMO};
+use crate::maquina::ponto_flutuante;
+use crate::montador::montador::montar;
+
+/// Carrega os bytes no endereço de carga padrão pelo mesmo carregador dos programas
+/// montados, como um programa objeto com os registros T necessários.
+fn carregar(maquina: &mut Maquina, programa: &[u8]) {
+    let mut objeto = format!("HTESTE 000000{:06X}\n", programa.len());
+    for (indice, bytes) in programa.chunks(30).enumerate() {
+        objeto.push_str(&format!("T{:06X}{:02X}", indice * 30, bytes.len()));
+        for byte in bytes {
+            objeto.push_str(&format!("{:02X}", byte));
+        }
+
+        objeto.push('\n');
+    }
+
+    objeto.push_str("E000000");
+    maquina.carregar_objeto(&objeto).unwrap();
+}
 
 #[test]
 fn add_imediato() {
     let mut maquina = Maquina::new();
-    maquina.carregar(&[0x19, 0x00, 0x01]).unwrap();
+    carregar(&mut maquina, &[0x19, 0x00, 0x01]);
     maquina.executar_instrucao().unwrap();
     assert_eq!(maquina.registrador(registradores::A), Some(1));
 }
@@ -12,11 +36,11 @@ fn add_imediato() {
 #[test]
 fn clear() {
     let mut maquina = Maquina::new();
-    maquina.carregar(&[0x19, 0x00, 0x01]).unwrap();
+    carregar(&mut maquina, &[0x19, 0x00, 0x01]);
     maquina.executar_instrucao().unwrap();
     assert_eq!(maquina.registrador(registradores::A), Some(1));
 
-    maquina.carregar(&[0x04, 0x00, 0x00]).unwrap();
+    carregar(&mut maquina, &[0xB4, 0x00]);
     maquina.executar_instrucao().unwrap();
     assert_eq!(maquina.registrador(registradores::A), Some(0));
 }
@@ -24,9 +48,10 @@ fn clear() {
 #[test]
 fn programa_com_multiplas_instrucoes() {
     let mut maquina = Maquina::new();
-    maquina
-        .carregar(&[0x19, 0x00, 0x01, 0x19, 0x00, 0x01, 0x19, 0x00, 0x01])
-        .unwrap();
+    carregar(
+        &mut maquina,
+        &[0x19, 0x00, 0x01, 0x19, 0x00, 0x01, 0x19, 0x00, 0x01],
+    );
 
     maquina.executar_instrucao().unwrap();
     assert_eq!(maquina.registrador(registradores::A), Some(1));
@@ -40,3 +65,719 @@ fn programa_com_multiplas_instrucoes() {
     // Assegurar que todas as instruções foram executadas
     assert!(maquina.executar_instrucao().is_err());
 }
+
+#[test]
+fn carregar_objeto_relocando() {
+    let mut maquina = Maquina::new();
+    maquina
+        .carregar_objeto(
+            "HT_MOD 000000000014\n\
+             T000000144B1000073F20094F000000000700001001100005\n\
+             M00000105+T_MOD\n\
+             M00000A06+T_MOD\n\
+             E000000",
+        )
+        .unwrap();
+
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x6000));
+    assert_eq!(
+        &maquina.memoria()[0x6000..0x6004],
+        &[0x4B, 0x10, 0x60, 0x07]
+    );
+    assert_eq!(&maquina.memoria()[0x600A..0x600D], &[0x00, 0x60, 0x07]);
+    assert_eq!(&maquina.memoria()[0x600D..0x6010], &[0x00, 0x00, 0x10]);
+}
+
+#[test]
+fn carregar_objeto_com_lacunas() {
+    let mut maquina = Maquina::new();
+    maquina
+        .carregar_objeto(
+            "HT_ORG 001000000013\n\
+             T0010000303200D\n\
+             T00101003000003\n\
+             T001003033F2FFA\n\
+             E001003",
+        )
+        .unwrap();
+
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x6003));
+    assert_eq!(
+        &maquina.memoria()[0x6000..0x6006],
+        &[0x03, 0x20, 0x0D, 0x3F, 0x2F, 0xFA]
+    );
+    assert_eq!(&maquina.memoria()[0x6010..0x6013], &[0x00, 0x00, 0x03]);
+}
+
+#[test]
+fn carregar_objeto_invalido() {
+    let mut maquina = Maquina::new();
+    for objeto in [
+        "HTESTE 000000000003\nT00000003000000\nM00000010\nE000000",
+        "HTESTE 000000000003\nT00000003000000\nM00000000\nE000000",
+        "HAé00000000000\nE000000",
+        "HTESTE 00000000000\nE000000",
+    ] {
+        assert!(maquina.carregar_objeto(objeto).is_err());
+    }
+}
+
+#[test]
+fn ligar_objetos() {
+    let proga = "HPROGA 00000000000D\n\
+                 DLISTA 000007ENDA  00000A\n\
+                 RLISTB ROTB  \n\
+                 T0000000D4B100000032000000004FFFFFC\n\
+                 M00000105+ROTB\n\
+                 M00000A06+LISTB\n\
+                 E000000";
+    let progb = "HPROGB 00000000000A\n\
+                 DLISTB 000007ROTB  000000\n\
+                 RLISTA ENDA  \n\
+                 T0000000A031000004F0000000000\n\
+                 M00000105+LISTA\n\
+                 M00000706+ENDA\n\
+                 M00000706-LISTA\n\
+                 E";
+
+    let mut maquina = Maquina::new();
+    maquina.ligar_objetos(&[proga, progb], 0x4000).unwrap();
+
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x4000));
+    assert_eq!(
+        &maquina.memoria()[0x4000..0x4004],
+        &[0x4B, 0x10, 0x40, 0x0D]
+    );
+    assert_eq!(&maquina.memoria()[0x400A..0x400D], &[0x00, 0x40, 0x10]);
+    assert_eq!(
+        &maquina.memoria()[0x400D..0x4011],
+        &[0x03, 0x10, 0x40, 0x07]
+    );
+    assert_eq!(&maquina.memoria()[0x4014..0x4017], &[0x00, 0x00, 0x03]);
+
+    // Sem a outra seção as referências externas não podem ser resolvidas
+    assert!(maquina.ligar_objetos(&[proga], 0x4000).is_err());
+}
+
+#[test]
+fn aritmetica_com_sinal() {
+    let mut maquina = Maquina::new();
+    carregar(
+        &mut maquina,
+        &[
+            0x01, 0x00, 0x05, // LDA #5
+            0x1D, 0x00, 0x07, // SUB #7
+            0x21, 0x00, 0x03, // MUL #3
+            0x25, 0x00, 0x04, // DIV #4
+            0x29, 0x00, 0x00, // COMP #0
+        ],
+    );
+
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0xFFFFFE));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0xFFFFFA));
+
+    // -6 / 4 é arredondado em direção ao zero
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0xFFFFFF));
+
+    // Números negativos são menores que zero, CC = 11
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x830000));
+}
+
+#[test]
+fn subtracao_registradores() {
+    let mut maquina = Maquina::new();
+    carregar(
+        &mut maquina,
+        &[
+            0x01, 0x00, 0x0A, // LDA #10
+            0x6D, 0x00, 0x03, // LDS #3
+            0x94, 0x40, // SUBR S,A
+        ],
+    );
+
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(7));
+}
+
+#[test]
+fn divisao_por_zero() {
+    let mut maquina = Maquina::new();
+    carregar(
+        &mut maquina,
+        &[
+            0x01, 0x00, 0x05, // LDA #5
+            0x25, 0x00, 0x00, // DIV #0
+        ],
+    );
+
+    maquina.executar_instrucao().unwrap();
+    let erro = maquina.executar_instrucao().unwrap_err();
+    assert_eq!(erro.downcast_ref::<Falha>(), Some(&Falha::DivisaoPorZero));
+}
+
+#[test]
+fn formato_ponto_flutuante() {
+    // 1,5 = 0,75 * 2^1
+    assert_eq!(ponto_flutuante::de_f64(1.5).unwrap(), 0x401C00000000);
+    assert_eq!(ponto_flutuante::de_f64(-1.5).unwrap(), 0xC01C00000000);
+    assert_eq!(ponto_flutuante::de_f64(0.0).unwrap(), 0);
+    assert_eq!(ponto_flutuante::para_f64(0x401C00000000), 1.5);
+
+    // Fração não normalizada: 0,375 * 2^1
+    assert_eq!(ponto_flutuante::para_f64(0x401600000000), 0.75);
+    assert!(ponto_flutuante::de_f64(f64::MAX).is_err());
+}
+
+#[test]
+fn instrucoes_ponto_flutuante() {
+    let mut programa = vec![
+        0x01, 0x00, 0x03, // LDA #3
+        0xC0, // FLOAT
+        0x5B, 0x10, 0x60, 0x20, // +ADDF 6020
+        0x63, 0x10, 0x60, 0x20, // +MULF 6020
+        0xC4, // FIX
+        0x83, 0x10, 0x60, 0x30, // +STF 6030
+        0x8B, 0x10, 0x60, 0x20, // +COMPF 6020
+    ];
+
+    programa.resize(0x20, 0);
+    programa.extend([0x40, 0x1C, 0x00, 0x00, 0x00, 0x00]); // 1,5
+    programa.resize(0x36, 0);
+
+    let mut maquina = Maquina::new();
+    carregar(&mut maquina, &programa);
+
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::F), Some(0x402C00000000));
+
+    // (3 + 1,5) * 1,5 = 6,75
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::F), Some(0x403D80000000));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(6));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(
+        &maquina.memoria()[0x6030..0x6036],
+        &[0x40, 0x3D, 0x80, 0x00, 0x00, 0x00]
+    );
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x810000));
+}
+
+#[test]
+fn entrada_e_saida_em_dispositivos() {
+    let mut programa = vec![
+        0x01, 0x00, 0x4F, // LDA #4F
+        0xDF, 0x10, 0x60, 0x20, // +WD 6020
+        0xE3, 0x10, 0x60, 0x20, // +TD 6020
+        0xDB, 0x10, 0x60, 0x20, // +RD 6020
+    ];
+
+    programa.resize(0x20, 0);
+    programa.push(0x07);
+
+    let fila = Fila::new();
+    let mut maquina = Maquina::new();
+    carregar(&mut maquina, &programa);
+    maquina.conectar_dispositivo(0x07, Box::new(fila.clone()));
+
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(fila.retirar_saida(), vec![0x4F]);
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x830000));
+
+    // Sem dados na fila, o RD espera sem avançar o PC
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x600B));
+
+    fila.adicionar_entrada(&[0x41]);
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0x41));
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x600F));
+}
+
+#[test]
+fn instrucoes_privilegiadas() {
+    let mut programa = vec![
+        0xEB, 0x10, 0x60, 0x40, // +STSW 6040
+        0xF8, // TIO
+        0xD5, 0x00, 0x64, // STI #100
+        0xB0, 0x30, // SVC 3
+        0xD3, 0x10, 0x60, 0x50, // +LPS 6050
+    ];
+
+    programa.resize(0x10, 0);
+    programa.push(0xF8); // TIO
+
+    // Status carregado pelo LPS: modo usuário, PC = 6010 e A = 5
+    programa.resize(0x50, 0);
+    programa.extend([0x00, 0x00, 0x00, 0x00, 0x60, 0x10, 0x00, 0x00, 0x05]);
+    programa.resize(0x6E, 0);
+
+    let mut maquina = Maquina::new();
+    carregar(&mut maquina, &programa);
+    assert!(maquina.modo_supervisor());
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(&maquina.memoria()[0x6040..0x6043], &[0x80, 0x00, 0x00]);
+
+    // Os canais estão sempre livres, CC = 11
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x830000));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::I), Some(100));
+
+    let erro = maquina.executar_instrucao().unwrap_err();
+    assert_eq!(
+        erro.downcast_ref::<Falha>(),
+        Some(&Falha::ChamadaSupervisor(3))
+    );
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x600A));
+
+    maquina.executar_instrucao().unwrap();
+    assert!(!maquina.modo_supervisor());
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x6010));
+    assert_eq!(maquina.registrador(registradores::A), Some(5));
+
+    let erro = maquina.executar_instrucao().unwrap_err();
+    assert_eq!(
+        erro.downcast_ref::<Falha>(),
+        Some(&Falha::InstrucaoPrivilegiada)
+    );
+}
+
+#[test]
+fn interrupcoes_svc_e_programa() {
+    // Áreas de trabalho em 100 e 130, a rotina do SVC retorna com LPS 106
+    let nucleo = "HNUCLEO000000000214\n\
+                  T00000004D3100040\n\
+                  T0000400600F000000080\n\
+                  T00008003B050F8\n\
+                  T00010006800000000200\n\
+                  T00013006800000000210\n\
+                  T00020004D3100106\n\
+                  E000000";
+
+    let mut maquina = Maquina::new();
+    maquina.ligar_objetos(&[nucleo], 0).unwrap();
+    maquina.habilitar_interrupcoes(true);
+
+    // LPS para o programa do usuário em 80
+    maquina.executar_instrucao().unwrap();
+    assert!(!maquina.modo_supervisor());
+
+    // SVC 5
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x800005));
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x200));
+    assert_eq!(
+        &maquina.memoria()[0x106..0x10C],
+        &[0x00, 0xF0, 0x00, 0x00, 0x00, 0x82]
+    );
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x00F000));
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x82));
+
+    // TIO em modo usuário, o PC salvo é o da instrução que falhou
+    maquina.executar_instrucao().unwrap();
+    let codigo = interrupcoes::CODIGO_INSTRUCAO_PRIVILEGIADA as u64;
+    assert_eq!(
+        maquina.registrador(registradores::SW),
+        Some(0x800000 | codigo)
+    );
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x210));
+    assert_eq!(&maquina.memoria()[0x139..0x13C], &[0x00, 0x00, 0x82]);
+}
+
+#[test]
+fn interrupcoes_temporizador_e_entrada_saida() {
+    // STI #3, LDA #2, SIO e LPS 40, as interrupções ficam pendentes até o LPS
+    // carregar um SW que permita elas
+    let nucleo = "HNUCLEO000000000234\n\
+                  T0000000BD50003010002F0D3100040\n\
+                  T0000400600F000000080\n\
+                  T00016006800000000220\n\
+                  T00019006800000000230\n\
+                  T00022004D3100166\n\
+                  E000000";
+
+    let mut maquina = Maquina::new();
+    maquina.ligar_objetos(&[nucleo], 0).unwrap();
+    maquina.habilitar_interrupcoes(true);
+
+    for _ in 0..4 {
+        maquina.executar_instrucao().unwrap();
+    }
+
+    assert_eq!(maquina.registrador(registradores::I), Some(0));
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x80));
+
+    // O temporizador tem prioridade sobre a entrada e saída
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x220));
+    assert_eq!(&maquina.memoria()[0x169..0x16C], &[0x00, 0x00, 0x80]);
+
+    // A rotina roda com as interrupções mascaradas até retornar
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x80));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::SW), Some(0x800002));
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x230));
+}
+
+#[test]
+fn protecao_memoria() {
+    let mut programa = vec![
+        0x01, 0x00, 0x03, // LDA #3
+        0xEF, 0x10, 0x68, 0x00, // +SSK 6800
+        0xD3, 0x10, 0x60, 0x40, // +LPS 6040
+    ];
+
+    programa.resize(0x10, 0);
+    programa.extend([
+        0x83, 0x10, 0x68, 0x00, // +STF 6800
+        0x83, 0x10, 0x60, 0x00, // +STF 6000
+    ]);
+
+    // Status em modo usuário com a chave 3, PC = 6010 e F = 1,5
+    programa.resize(0x40, 0);
+    programa.extend([0x0C, 0x00, 0x00, 0x00, 0x60, 0x10]);
+    programa.resize(0x58, 0);
+    programa.extend([0x40, 0x1C, 0x00, 0x00, 0x00, 0x00]);
+
+    let mut maquina = Maquina::new();
+    carregar(&mut maquina, &programa);
+
+    for _ in 0..4 {
+        maquina.executar_instrucao().unwrap();
+    }
+
+    // O bloco em 6800 tem a mesma chave do SW
+    assert_eq!(
+        &maquina.memoria()[0x6800..0x6806],
+        &[0x40, 0x1C, 0x00, 0x00, 0x00, 0x00]
+    );
+
+    let erro = maquina.executar_instrucao().unwrap_err();
+    assert_eq!(erro.downcast_ref::<Falha>(), Some(&Falha::ProtecaoMemoria));
+    assert_eq!(&maquina.memoria()[0x6000..0x6003], &[0x01, 0x00, 0x03]);
+}
+
+#[test]
+fn memoria_configuravel() {
+    let mut programa = vec![0x03, 0x18, 0x00, 0x10]; // +LDA 80010
+    programa.resize(0x10, 0);
+    programa.extend([0x00, 0x00, 0x2A]);
+
+    let mut maquina = Maquina::construtor()
+        .tamanho_memoria(TAMANHO_MEMORIA_MAXIMO)
+        .endereco_carga(0x80000)
+        .construir()
+        .unwrap();
+
+    carregar(&mut maquina, &programa);
+    assert_eq!(maquina.memoria().len(), 0x100000);
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x80000));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0x2A));
+
+    // Na memória padrão de 32 KB o mesmo endereço não existe
+    let mut maquina = Maquina::new();
+    carregar(&mut maquina, &programa);
+    assert!(maquina.executar_instrucao().is_err());
+
+    assert!(
+        Maquina::construtor()
+            .tamanho_memoria(16384)
+            .construir()
+            .is_err()
+    );
+    assert!(
+        Maquina::construtor()
+            .tamanho_memoria(40000)
+            .construir()
+            .is_err()
+    );
+    assert!(
+        Maquina::construtor()
+            .endereco_carga(0x8000)
+            .construir()
+            .is_err()
+    );
+}
+
+#[test]
+fn executar_relativo_pc() {
+    let relativo = include_str!("../../programas_exemplo/relativo.asm");
+    let montagem = montar(relativo).unwrap();
+
+    let mut maquina = Maquina::new();
+    maquina.carregar_objeto(&montagem.objeto).unwrap();
+
+    for _ in 0..3 {
+        maquina.executar_instrucao().unwrap();
+    }
+
+    assert_eq!(maquina.registrador(registradores::A), Some(10));
+    assert_eq!(&maquina.memoria()[0x600F..0x6012], &[0x00, 0x00, 0x0A]);
+
+    // O J volta para o início com deslocamento negativo
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x6000));
+}
+
+#[test]
+fn executar_subrotina() {
+    let subrotina = include_str!("../../programas_exemplo/subrotina.asm");
+    let montagem = montar(subrotina).unwrap();
+
+    let mut maquina = Maquina::new();
+    maquina.carregar_objeto(&montagem.objeto).unwrap();
+
+    // JSUB salva no L o endereço da instrução seguinte
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x6012));
+    assert_eq!(maquina.registrador(registradores::L), Some(0x6006));
+
+    for _ in 0..16 {
+        maquina.executar_instrucao().unwrap();
+    }
+
+    assert_eq!(maquina.registrador(registradores::X), Some(3));
+    assert_eq!(&maquina.memoria()[0x601E..0x6021], &[0x00, 0x00, 0x06]);
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x601B));
+
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x601B));
+}
+
+#[test]
+fn decodificar_instrucoes() {
+    let mut memoria = vec![
+        0x4B, 0x10, 0x10, 0x36, // +JSUB 1036
+        0x57, 0xC0, 0x03, // STCH BUFFER,X com base
+        0xA0, 0x04, // COMPR A,S
+        0x03, 0x30, 0x00, // LDA com as flags b e p
+    ];
+    memoria.resize(0x1000, 0);
+    memoria.extend([0x3E, 0x2F, 0xEC]); // J @ com deslocamento -20
+
+    let jsub = instrucao::decodificar(&memoria, 0).unwrap();
+    assert_eq!(jsub.formato, Formato::Quatro);
+    assert_eq!(jsub.opcode, 0x48);
+    assert!(jsub.flags.n && jsub.flags.i && jsub.flags.e);
+    assert_eq!(jsub.endereco_alvo(&[0; 10]), Some(0x1036));
+
+    let mut registradores = [0; 10];
+    registradores[registradores::B] = 0x33;
+    registradores[registradores::X] = 2;
+    let stch = instrucao::decodificar(&memoria, 4).unwrap();
+    assert_eq!(stch.tamanho(), 3);
+    assert!(stch.flags.x && stch.flags.b);
+    assert_eq!(stch.enderecamento(), Some(Enderecamento::Simples));
+    assert_eq!(stch.endereco_alvo(&registradores), Some(0x38));
+
+    let compr = instrucao::decodificar(&memoria, 7).unwrap();
+    assert_eq!(compr.formato, Formato::Dois);
+    assert_eq!((compr.r1, compr.r2), (0, 4));
+    assert_eq!(compr.endereco_alvo(&registradores), None);
+
+    assert!(instrucao::decodificar(&memoria, 9).is_err());
+
+    let j = instrucao::decodificar(&memoria, 0x1000).unwrap();
+    assert_eq!(j.enderecamento(), Some(Enderecamento::Indireto));
+    assert_eq!(j.deslocamento_com_sinal(), -20);
+    assert_eq!(j.endereco_alvo(&registradores), Some(0xFEF));
+}
+
+#[test]
+fn deslocamentos() {
+    let mut maquina = Maquina::new();
+    carregar(
+        &mut maquina,
+        &[
+            0x01, 0x18, 0x00, 0x01, // +LDA #80001
+            0xA4, 0x03, // SHIFTL A,4
+            0xA8, 0x03, // SHIFTR A,4
+            0xA4, 0x07, // SHIFTL A,8
+            0xC5, // Opcode de formato 1 com os bits n e i
+        ],
+    );
+
+    maquina.executar_instrucao().unwrap();
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0x800010));
+
+    // O SHIFTR repete o bit de sinal e o SHIFTL é circular
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0xF80001));
+    maquina.executar_instrucao().unwrap();
+    assert_eq!(maquina.registrador(registradores::A), Some(0x0001F8));
+
+    let erro = maquina.executar_instrucao().unwrap_err();
+    assert_eq!(erro.downcast_ref(), Some(&Falha::InstrucaoInvalida));
+}
+
+#[test]
+fn desmontar_programas() {
+    let add = include_str!("../../programas_exemplo/add.hex");
+    let linhas = desmontador::desmontar_hex(add, 0).unwrap();
+    let assembly: Vec<String> = linhas.iter().map(LinhaDesmontada::assembly).collect();
+    assert_eq!(assembly.len(), 4);
+    assert_eq!(assembly[0], "         ADD     #1");
+    assert_eq!(assembly[3], "         STA     #8");
+
+    // As referências externas aparecem pelos registros M
+    let secoes = include_str!("../../programas_exemplo/secoes.asm");
+    let linhas = desmontador::desmontar_objeto(&montar(secoes).unwrap().objeto).unwrap();
+    assert_eq!(linhas[0].assembly(), "PROGA    +JSUB   ROTB");
+    assert_eq!(linhas[0].alvo, None);
+    assert_eq!(linhas[1].assembly(), "         LDA     LISTA");
+    assert_eq!(linhas[1].alvo, Some(0x07));
+
+    let subrotina = include_str!("../../programas_exemplo/subrotina.asm");
+    let montagem = montar(subrotina).unwrap();
+    let mut maquina = Maquina::new();
+    maquina.carregar_objeto(&montagem.objeto).unwrap();
+    maquina.adicionar_simbolos(&montagem.simbolos);
+
+    let linhas =
+        desmontador::desmontar(maquina.memoria(), 0x6003, 0x6012, None, maquina.simbolos());
+
+    assert_eq!(
+        linhas[0].to_string(),
+        "006003  4B200C    LACO     JSUB    DOBRAR       006012"
+    );
+    assert_eq!(linhas[4].assembly(), "         J       @FIMPTR");
+    assert_eq!(linhas[4].bytes, [0x3E, 0x20, 0x06]);
+}
+
+#[test]
+fn executar_ate_parar() {
+    let subrotina = include_str!("../../programas_exemplo/subrotina.asm");
+    let montagem = montar(subrotina).unwrap();
+
+    let mut maquina = Maquina::new();
+    maquina.carregar_objeto(&montagem.objeto).unwrap();
+    maquina.adicionar_simbolos(&montagem.simbolos);
+    assert!(maquina.adicionar_ponto_parada_simbolo("NADA").is_err());
+    assert_eq!(
+        maquina.adicionar_ponto_parada_simbolo("DOBRAR").unwrap(),
+        0x6012
+    );
+
+    // A sub-rotina é chamada 3 vezes e a execução continua do ponto de parada
+    for x in 0..3 {
+        let motivo = maquina.executar_ate_parar(100);
+        assert!(matches!(motivo, MotivoParada::PontoParada(0x6012)));
+        assert_eq!(maquina.registrador(registradores::X), Some(x));
+    }
+
+    assert!(!maquina.alternar_ponto_parada(0x6012));
+    assert!(matches!(
+        maquina.executar_ate_parar(3),
+        MotivoParada::LimitePassos
+    ));
+
+    // O FIM J FIM termina o programa
+    assert!(matches!(maquina.executar_ate_parar(100), MotivoParada::Fim));
+    assert_eq!(maquina.registrador(registradores::PC), Some(0x601B));
+    assert_eq!(&maquina.memoria()[0x601E..0x6021], &[0x00, 0x00, 0x06]);
+
+    carregar(
+        &mut maquina,
+        &[
+            0x01, 0x00, 0x05, // LDA #5
+            0x25, 0x00, 0x00, // DIV #0
+        ],
+    );
+
+    match maquina.executar_ate_parar(100) {
+        MotivoParada::Erro(erro) => {
+            assert_eq!(erro.downcast_ref(), Some(&Falha::DivisaoPorZero))
+        }
+        motivo => panic!("Motivo inesperado: {:?}", motivo),
+    }
+}
+
+#[test]
+fn executar_ate_parar_passo_a_passo() {
+    let subrotina = include_str!("../../programas_exemplo/subrotina.asm");
+    let montagem = montar(subrotina).unwrap();
+
+    let mut maquina = Maquina::new();
+    maquina.carregar_objeto(&montagem.objeto).unwrap();
+    maquina.adicionar_ponto_parada(0x6000);
+    maquina.adicionar_ponto_parada(0x6012);
+
+    // O ponto de parada na primeira instrução também para a execução
+    let pc = |maquina: &Maquina| maquina.registrador(registradores::PC);
+    assert!(matches!(
+        maquina.executar_ate_parar(1),
+        MotivoParada::PontoParada(0x6000)
+    ));
+    assert!(matches!(
+        maquina.executar_ate_parar(1),
+        MotivoParada::LimitePassos
+    ));
+    assert_eq!(pc(&maquina), Some(0x6003));
+
+    // Um passo de cada vez até o ponto de parada, que só é executado no passo seguinte
+    assert!(matches!(
+        maquina.executar_ate_parar(1),
+        MotivoParada::LimitePassos
+    ));
+    assert_eq!(pc(&maquina), Some(0x6012));
+    assert!(matches!(
+        maquina.executar_ate_parar(1),
+        MotivoParada::PontoParada(0x6012)
+    ));
+    assert!(matches!(
+        maquina.executar_ate_parar(1),
+        MotivoParada::LimitePassos
+    ));
+    assert_eq!(pc(&maquina), Some(0x6015));
+    assert_eq!(maquina.registrador(registradores::A), Some(2));
+
+    // Com limite 2 o JSUB chega no ponto de parada no fim de uma chamada e para no
+    // começo da próxima
+    assert!(matches!(
+        maquina.executar_ate_parar(2),
+        MotivoParada::LimitePassos
+    ));
+    assert!(matches!(
+        maquina.executar_ate_parar(2),
+        MotivoParada::LimitePassos
+    ));
+    assert_eq!(pc(&maquina), Some(0x6012));
+    assert!(matches!(
+        maquina.executar_ate_parar(2),
+        MotivoParada::PontoParada(0x6012)
+    ));
+    assert!(matches!(
+        maquina.executar_ate_parar(2),
+        MotivoParada::LimitePassos
+    ));
+    assert_eq!(pc(&maquina), Some(0x6006));
+    assert_eq!(maquina.registrador(registradores::A), Some(4));
+}
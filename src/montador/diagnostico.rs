@@ -0,0 +1,159 @@
+use std::fmt;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severidade {
+    Erro,
+    Aviso,
+}
+
+/// Tipos de problema que o montador pode encontrar. Cada um tem um código fixo,
+/// com E para erros e W para avisos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Codigo {
+    OperacaoInvalida,
+    SimboloDuplicado,
+    OperandoInvalido,
+    ForaDoAlcance,
+    ReferenciaExternaInvalida,
+    NomeInvalido,
+    LinhaSemOperacao,
+}
+
+impl Codigo {
+    pub fn identificador(&self) -> &'static str {
+        match self {
+            Codigo::OperacaoInvalida => "E001",
+            Codigo::SimboloDuplicado => "E002",
+            Codigo::OperandoInvalido => "E003",
+            Codigo::ForaDoAlcance => "E004",
+            Codigo::ReferenciaExternaInvalida => "E005",
+            Codigo::NomeInvalido => "E006",
+            Codigo::LinhaSemOperacao => "W001",
+        }
+    }
+
+    pub fn severidade(&self) -> Severidade {
+        match self {
+            Codigo::LinhaSemOperacao => Severidade::Aviso,
+            _ => Severidade::Erro,
+        }
+    }
+}
+
+/// Problema encontrado pelo montador em uma linha do código fonte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostico {
+    pub severidade: Severidade,
+    pub codigo: Codigo,
+    pub mensagem: String,
+    /// Número da linha no código fonte, começando em 1
+    pub linha: usize,
+    /// Colunas da parte da linha com o problema, em caracteres e começando em 0
+    pub colunas: Range<usize>,
+    /// Texto da linha, usado para exibir o diagnóstico
+    pub fonte: String,
+}
+
+impl Diagnostico {
+    pub fn new(
+        codigo: Codigo,
+        mensagem: String,
+        linha: usize,
+        colunas: Range<usize>,
+        fonte: &str,
+    ) -> Self {
+        Self {
+            severidade: codigo.severidade(),
+            codigo,
+            mensagem,
+            linha,
+            colunas,
+            fonte: fonte.to_string(),
+        }
+    }
+}
+
+/// Exibe o diagnóstico com a linha do código fonte e a parte com o problema sublinhada:
+///
+/// ```text
+/// erro[E001]: Operação inválida: LDZ
+///  --> linha 5, coluna 13
+///   |
+/// 5 |             LDZ ALPHA
+///   |             ^^^
+/// ```
+impl fmt::Display for Diagnostico {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severidade = match self.severidade {
+            Severidade::Erro => "erro",
+            Severidade::Aviso => "aviso",
+        };
+
+        let numero = self.linha.to_string();
+        let margem = " ".repeat(numero.len());
+        let fonte = self.fonte.trim_end();
+
+        // Partes vazias, como um operando que falta, são marcadas com um único ^
+        let inicio = self.colunas.start;
+        let tamanho = self.colunas.len().max(1);
+
+        writeln!(
+            f,
+            "{severidade}[{}]: {}",
+            self.codigo.identificador(),
+            self.mensagem
+        )?;
+        writeln!(f, "{margem}--> linha {}, coluna {}", self.linha, inicio + 1)?;
+        writeln!(f, "{margem} |")?;
+        writeln!(f, "{numero} | {fonte}")?;
+        write!(
+            f,
+            "{margem} | {}{}",
+            " ".repeat(inicio),
+            "^".repeat(tamanho)
+        )
+    }
+}
+
+/// Erros encontrados durante a montagem. Contém todos os diagnósticos do passo que
+/// falhou, inclusive os avisos.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErroMontagem {
+    pub diagnosticos: Vec<Diagnostico>,
+}
+
+impl ErroMontagem {
+    /// Retorna um erro caso algum dos diagnósticos seja um erro.
+    pub fn verificar(diagnosticos: &[Diagnostico]) -> Result<(), ErroMontagem> {
+        if diagnosticos
+            .iter()
+            .any(|diagnostico| diagnostico.severidade == Severidade::Erro)
+        {
+            return Err(ErroMontagem {
+                diagnosticos: diagnosticos.to_vec(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for ErroMontagem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let erros = self
+            .diagnosticos
+            .iter()
+            .filter(|diagnostico| diagnostico.severidade == Severidade::Erro)
+            .count();
+
+        write!(f, "Montagem falhou com {erros} erro(s)")?;
+        for diagnostico in &self.diagnosticos {
+            write!(f, "\n\n{diagnostico}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for ErroMontagem {}
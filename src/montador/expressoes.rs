@@ -0,0 +1,283 @@
+use anyhow::{Context, anyhow};
+use std::collections::{BTreeMap, HashMap};
+
+/// Durante o primeiro passo os endereços relativos são guardados como
+/// (bloco << BITS_BLOCO) + deslocamento dentro do bloco, já que o início
+/// de cada bloco só é conhecido no final do passo.
+pub const BITS_BLOCO: u32 = 32;
+pub const MASCARA_BLOCO: usize = (1 << BITS_BLOCO) - 1;
+
+/// Valor de um símbolo ou expressão. Valores relativos dependem do endereço
+/// onde o programa é carregado, enquanto valores absolutos são constantes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Valor {
+    pub valor: isize,
+    pub relativo: bool,
+}
+
+impl Valor {
+    pub fn absoluto(valor: isize) -> Self {
+        Self {
+            valor,
+            relativo: false,
+        }
+    }
+
+    pub fn relativo(valor: isize) -> Self {
+        Self {
+            valor,
+            relativo: true,
+        }
+    }
+}
+
+/// Símbolo definido em outra seção de controle usado em uma expressão. O seu valor
+/// só é conhecido pelo carregador, que soma ou subtrai o endereço dele do campo.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReferenciaExterna<'a> {
+    pub simbolo: &'a str,
+    pub negativa: bool,
+}
+
+enum Token<'a> {
+    Numero(isize),
+    Simbolo(&'a str),
+    Operador(char),
+}
+
+/// Avalia uma expressão com números decimais, símbolos, `*` (contador de localização)
+/// e os operadores +, -, * e /.
+///
+/// Termos relativos só podem aparecer em pares de sinais opostos do mesmo bloco, com no
+/// máximo um termo relativo positivo sobrando, e não podem ser usados em multiplicações
+/// e divisões.
+pub fn avaliar(
+    expressao: &str,
+    simbolos: &HashMap<&str, Valor>,
+    contador_localizacao: usize,
+) -> anyhow::Result<Valor> {
+    let (valor, _) = avaliar_com_externos(expressao, simbolos, &[], contador_localizacao)?;
+    Ok(valor)
+}
+
+/// Avalia uma expressão que pode usar símbolos externos (EXTREF). Eles contam como
+/// zero no valor retornado e são devolvidos separadamente, com o sinal com que aparecem.
+pub fn avaliar_com_externos<'a>(
+    expressao: &'a str,
+    simbolos: &HashMap<&str, Valor>,
+    externos: &[&str],
+    contador_localizacao: usize,
+) -> anyhow::Result<(Valor, Vec<ReferenciaExterna<'a>>)> {
+    let tokens = separar_tokens(expressao)?;
+    let mut avaliador = Avaliador {
+        tokens: &tokens,
+        posicao: 0,
+        simbolos,
+        externos,
+        contador_localizacao,
+        sinal: 1,
+        referencias: Vec::new(),
+    };
+
+    let (valor, relatividade) = avaliador.expressao()?;
+    if avaliador.posicao < tokens.len() {
+        return Err(anyhow!("Expressão inválida: {}", expressao));
+    }
+
+    // A distância entre blocos só é conhecida no final do primeiro passo
+    let relatividade: Vec<isize> = relatividade.into_values().filter(|r| *r != 0).collect();
+    let valor = match relatividade[..] {
+        [] => Valor::absoluto(valor),
+        [1] => Valor::relativo(valor),
+        [_, _, ..] => {
+            return Err(anyhow!(
+                "Expressão inválida, termos relativos de blocos diferentes: {}",
+                expressao
+            ));
+        }
+        _ => {
+            return Err(anyhow!(
+                "Expressão inválida, termos relativos não pareados: {}",
+                expressao
+            ));
+        }
+    };
+
+    Ok((valor, avaliador.referencias))
+}
+
+fn separar_tokens(expressao: &str) -> anyhow::Result<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut resto = expressao.trim();
+
+    while let Some(c) = resto.chars().next() {
+        if c.is_whitespace() {
+            resto = resto.trim_start();
+        } else if matches!(c, '+' | '-' | '*' | '/') {
+            tokens.push(Token::Operador(c));
+            resto = &resto[1..];
+        } else if c.is_ascii_alphanumeric() || c == '_' {
+            let fim = resto
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(resto.len());
+
+            let (termo, restante) = resto.split_at(fim);
+            if c.is_ascii_digit() {
+                let Ok(numero) = termo.parse::<isize>() else {
+                    return Err(anyhow!("Número inválido: {}", termo));
+                };
+
+                tokens.push(Token::Numero(numero));
+            } else {
+                tokens.push(Token::Simbolo(termo));
+            }
+
+            resto = restante;
+        } else {
+            return Err(anyhow!("Caractere inválido na expressão: {}", c));
+        }
+    }
+
+    if tokens.is_empty() {
+        return Err(anyhow!("Expressão vazia"));
+    }
+
+    Ok(tokens)
+}
+
+/// Quantidade de termos relativos de cada bloco, com o sinal deles.
+type Relatividade = BTreeMap<usize, isize>;
+
+/// Avaliador recursivo. Cada termo é representado pelo seu valor e sua
+/// relatividade, vazia nos termos absolutos.
+struct Avaliador<'a, 'b> {
+    tokens: &'a [Token<'b>],
+    posicao: usize,
+    simbolos: &'a HashMap<&'a str, Valor>,
+    externos: &'a [&'a str],
+    contador_localizacao: usize,
+    /// Sinal do termo sendo avaliado
+    sinal: isize,
+    referencias: Vec<ReferenciaExterna<'b>>,
+}
+
+impl<'b> Avaliador<'_, 'b> {
+    fn operador(&self) -> Option<char> {
+        match self.tokens.get(self.posicao) {
+            Some(Token::Operador(operador)) => Some(*operador),
+            _ => None,
+        }
+    }
+
+    fn expressao(&mut self) -> anyhow::Result<(isize, Relatividade)> {
+        let mut sinal = 1;
+        if let Some(operador @ ('+' | '-')) = self.operador() {
+            sinal = if operador == '-' { -1 } else { 1 };
+            self.posicao += 1;
+        }
+
+        let mut valor: isize = 0;
+        let mut relatividade = Relatividade::new();
+
+        loop {
+            self.sinal = sinal;
+            let (termo, relatividade_termo) = self.termo()?;
+            valor = termo
+                .checked_mul(sinal)
+                .and_then(|termo| valor.checked_add(termo))
+                .context("Estouro no valor da expressão")?;
+            for (bloco, quantidade) in relatividade_termo {
+                *relatividade.entry(bloco).or_default() += sinal * quantidade;
+            }
+
+            match self.operador() {
+                Some('+') => sinal = 1,
+                Some('-') => sinal = -1,
+                _ => break,
+            }
+
+            self.posicao += 1;
+        }
+
+        Ok((valor, relatividade))
+    }
+
+    fn termo(&mut self) -> anyhow::Result<(isize, Relatividade)> {
+        let referencias = self.referencias.len();
+        let (mut valor, relatividade) = self.fator()?;
+
+        while let Some(operador @ ('*' | '/')) = self.operador() {
+            self.posicao += 1;
+            let (fator, relatividade_fator) = self.fator()?;
+
+            if self.referencias.len() != referencias {
+                return Err(anyhow!(
+                    "Referências externas não podem ser usadas em multiplicações ou divisões"
+                ));
+            }
+
+            if !relatividade.is_empty() || !relatividade_fator.is_empty() {
+                return Err(anyhow!(
+                    "Termos relativos não podem ser usados em multiplicações ou divisões"
+                ));
+            }
+
+            valor = if operador == '*' {
+                valor
+                    .checked_mul(fator)
+                    .context("Estouro no valor da expressão")?
+            } else if fator == 0 {
+                return Err(anyhow!("Divisão por zero na expressão"));
+            } else {
+                // Só estoura no menor valor dividido por -1
+                valor
+                    .checked_div(fator)
+                    .context("Estouro no valor da expressão")?
+            };
+        }
+
+        Ok((valor, relatividade))
+    }
+
+    fn fator(&mut self) -> anyhow::Result<(isize, Relatividade)> {
+        let token = self.tokens.get(self.posicao);
+        self.posicao += 1;
+
+        match token {
+            Some(Token::Numero(numero)) => Ok((*numero, Relatividade::new())),
+            Some(Token::Simbolo(simbolo)) => {
+                if let Some(valor) = self.simbolos.get(simbolo) {
+                    if !valor.relativo {
+                        return Ok((valor.valor, Relatividade::new()));
+                    }
+
+                    return Ok((valor.valor, relativo(valor.valor)));
+                }
+
+                if !self.externos.contains(simbolo) {
+                    return Err(anyhow!("Símbolo não encontrado: {}", simbolo));
+                }
+
+                self.referencias.push(ReferenciaExterna {
+                    simbolo,
+                    negativa: self.sinal < 0,
+                });
+
+                Ok((0, Relatividade::new()))
+            }
+
+            // Contador de localização
+            Some(Token::Operador('*')) => {
+                let contador = self.contador_localizacao as isize;
+                Ok((contador, relativo(contador)))
+            }
+
+            _ => Err(anyhow!("Expressão inválida, esperado um termo")),
+        }
+    }
+}
+
+/// Relatividade de um termo relativo, no bloco do endereço dele.
+fn relativo(endereco: isize) -> Relatividade {
+    Relatividade::from([(endereco as usize >> BITS_BLOCO, 1)])
+}
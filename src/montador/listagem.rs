@@ -0,0 +1,130 @@
+use crate::montador::montador::Tabelas;
+use std::fmt::Write;
+
+/// Linha da listagem gerada pelo segundo passo.
+#[derive(Debug, PartialEq)]
+pub struct LinhaListagem<'a> {
+    /// Índice da linha no código fonte
+    pub indice: usize,
+    pub endereco: Option<usize>,
+    /// Literal colocado pelo LTORG ou no final da seção, listado em uma linha própria
+    pub literal: Option<&'a str>,
+    pub codigo: String,
+}
+
+impl<'a> LinhaListagem<'a> {
+    /// Linha do código fonte sem código objeto.
+    pub fn fonte(indice: usize, endereco: Option<usize>) -> Self {
+        Self {
+            indice,
+            endereco,
+            literal: None,
+            codigo: String::new(),
+        }
+    }
+
+    pub fn literal(indice: usize, literal: &'a str, endereco: usize, codigo: String) -> Self {
+        Self {
+            indice,
+            endereco: Some(endereco),
+            literal: Some(literal),
+            codigo,
+        }
+    }
+}
+
+/// Gera a listagem do programa: cada linha do código fonte com o seu número, o contador
+/// de localização e o código objeto gerado, seguida pelas tabelas de símbolos e de
+/// literais de cada seção, em ordem alfabética.
+///
+/// As linhas da listagem devem estar na ordem do código fonte.
+pub fn gerar_listagem(
+    assembly: &str,
+    linhas: &[LinhaListagem],
+    nomes_secoes: &[&str],
+    tabelas: &[Tabelas],
+) -> String {
+    let mut listagem = format!(
+        "{:>5}  {:<6}  {:<40} {}\n",
+        "Linha", "Ender", "Código fonte", "Código objeto"
+    );
+
+    let mut linhas = linhas.iter().peekable();
+    for (indice, fonte) in assembly.lines().enumerate() {
+        let fonte = fonte.replace('\t', "    ");
+        let mut listada = false;
+
+        while let Some(linha) = linhas.next_if(|linha| linha.indice <= indice) {
+            let numero = if linha.literal.is_some() {
+                String::new()
+            } else {
+                listada = true;
+                (indice + 1).to_string()
+            };
+
+            let fonte = match linha.literal {
+                Some(literal) => format!("*       {}", literal),
+                None => fonte.trim_end().to_string(),
+            };
+
+            let endereco = linha
+                .endereco
+                .map(|endereco| format!("{:06X}", endereco))
+                .unwrap_or_default();
+
+            adicionar_linha(&mut listagem, &numero, &endereco, &fonte, &linha.codigo);
+        }
+
+        // Comentários, linhas vazias e linhas depois do END
+        if !listada {
+            adicionar_linha(&mut listagem, &(indice + 1).to_string(), "", &fonte, "");
+        }
+    }
+
+    for (nome, tabelas) in nomes_secoes.iter().zip(tabelas) {
+        let mut simbolos: Vec<_> = tabelas.simbolos.iter().collect();
+        simbolos.sort_by_key(|(simbolo, _)| **simbolo);
+
+        let _ = write!(
+            listagem,
+            "\nTabela de símbolos ({nome})\n{:<10}{:<8}Tipo\n",
+            "Símbolo", "Valor"
+        );
+
+        for (simbolo, valor) in simbolos {
+            let tipo = if valor.relativo { "R" } else { "A" };
+            let _ = writeln!(
+                listagem,
+                "{:<10}{:<8}{}",
+                simbolo,
+                format!("{:06X}", valor.valor & 0xFFFFFF),
+                tipo
+            );
+        }
+
+        if tabelas.literais.is_empty() {
+            continue;
+        }
+
+        let mut literais: Vec<_> = tabelas.literais.iter().collect();
+        literais.sort_by_key(|(literal, _)| **literal);
+
+        let _ = write!(
+            listagem,
+            "\nTabela de literais ({nome})\n{:<16}Endereço\n",
+            "Literal"
+        );
+
+        for (literal, endereco) in literais {
+            let _ = writeln!(listagem, "{:<16}{:06X}", literal, endereco);
+        }
+    }
+
+    listagem
+}
+
+fn adicionar_linha(listagem: &mut String, numero: &str, endereco: &str, fonte: &str, codigo: &str) {
+    let linha = format!("{:>5}  {:<6}  {:<40} {}", numero, endereco, fonte, codigo);
+    listagem.push_str(linha.trim_end());
+    listagem.push('\n');
+}
@@ -1,3 +1,6 @@
+pub mod diagnostico;
+pub mod expressoes;
+pub mod listagem;
 #[allow(clippy::module_inception)]
 pub mod montador; // Adicione 'pub' aqui
 pub mod tabela_operacoes; // Adicione 'pub' aqui
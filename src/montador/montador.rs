@@ -1,339 +1,1367 @@
-use crate::maquina::constantes::opcodes;
-use crate::montador::tabela_operacoes::{Operacao, TABELA_OPERACOES};
+use crate::maquina::conjunto_instrucoes::{DefinicaoInstrucao, Operandos};
+use crate::maquina::instrucao::Formato;
+use crate::maquina::maquina::TAMANHO_MEMORIA_MAXIMO;
+use crate::montador::diagnostico::{Codigo, Diagnostico, ErroMontagem};
+use crate::montador::expressoes::{self, BITS_BLOCO, MASCARA_BLOCO, ReferenciaExterna, Valor};
+use crate::montador::listagem::{self, LinhaListagem};
+use crate::montador::tabela_operacoes::{self, Operacao};
 use crate::montador::tabela_registradores::TABELA_REGISTRADORES;
 use anyhow::{Context, anyhow};
 use std::collections::HashMap;
 
-pub fn primeiro_passo(assembly: &str) -> anyhow::Result<HashMap<&str, usize>> {
-    // Pular linhas no começo que são só comentários
-    let mut linhas = assembly.lines().skip_while(|l| l.trim().starts_with("."));
-    let mut contador_localizacao = 0;
+/// Tabelas de uma seção de controle, montadas pelo primeiro passo e usadas pelo segundo.
+#[derive(Debug, Default, PartialEq)]
+pub struct Tabelas<'a> {
+    /// Labels e seus valores, normalmente endereços
+    pub simbolos: HashMap<&'a str, Valor>,
+    /// Literais (=C'..', =X'..' ou =valor) e seus endereços
+    pub literais: HashMap<&'a str, usize>,
+    /// Blocos de programa na ordem em que aparecem no programa objeto
+    pub blocos: Vec<Bloco<'a>>,
+    /// Símbolos desta seção exportados pelo EXTDEF
+    pub definicoes_externas: Vec<&'a str>,
+    /// Símbolos de outras seções importados pelo EXTREF
+    pub referencias_externas: Vec<&'a str>,
+}
 
-    if let Some(linha) = linhas.next() {
-        let mut linha = linha.split_whitespace();
-        // Pular nome do programa
-        linha.next();
+/// Bloco de programa definido pela diretiva USE. O bloco padrão não tem nome.
+#[derive(Debug, PartialEq)]
+pub struct Bloco<'a> {
+    pub nome: &'a str,
+    pub inicio: usize,
+    pub tamanho: usize,
+}
 
-        if let Some(operador) = linha.next()
-            && operador == "START"
-            && let Some(operando) = linha.next()
-        {
-            contador_localizacao = usize::from_str_radix(operando, 16).unwrap_or_default();
+/// Programa montado, com o programa objeto, a listagem e os avisos da montagem.
+#[derive(Debug, PartialEq)]
+pub struct Montagem {
+    pub objeto: String,
+    pub listagem: String,
+    pub avisos: Vec<Diagnostico>,
+    /// Endereço de cada label relativo ao início do programa carregado, com as seções
+    /// uma após a outra como o carregador de ligação as coloca
+    pub simbolos: Vec<(String, usize)>,
+}
+
+/// Seção de controle, iniciada pelo START ou por um CSECT.
+pub(super) struct Secao<'a> {
+    nome: &'a str,
+    endereco_inicial: usize,
+    /// Índice e texto da linha do START ou do CSECT no código fonte
+    indice_cabecalho: usize,
+    cabecalho: &'a str,
+    /// Linhas da seção e seus índices no código fonte
+    linhas: Vec<(usize, &'a str)>,
+}
+
+/// Parte da linha onde um erro ocorreu, que é sublinhada no diagnóstico.
+#[derive(Clone, Copy)]
+enum Parte {
+    Label,
+    Operacao,
+    Operando,
+    Linha,
+}
+
+/// Erro em uma linha. Erros do anyhow são considerados erros no operando.
+struct ErroLinha {
+    parte: Parte,
+    codigo: Codigo,
+    mensagem: String,
+}
+
+impl ErroLinha {
+    fn new(parte: Parte, codigo: Codigo, mensagem: impl Into<String>) -> Self {
+        Self {
+            parte,
+            codigo,
+            mensagem: mensagem.into(),
         }
     }
 
-    let mut tabela_simbolos = HashMap::new();
-    for linha in linhas {
-        // Remover comentários
-        let linha = linha
-            .split_once(".")
-            .map(|(linha, _)| linha)
-            .unwrap_or(linha);
+    /// Converte o erro em um diagnóstico da linha com o índice e o texto informados.
+    fn diagnostico(self, indice: usize, fonte: &str, linha: Option<&Linha>) -> Diagnostico {
+        let parte = match (self.parte, linha) {
+            (Parte::Label, Some(linha)) => linha.label.unwrap_or(linha.operacao),
+            (Parte::Operacao, Some(linha)) => linha.operacao,
+            (Parte::Operando, Some(linha)) => linha.operando,
+            _ => fonte.trim(),
+        };
+
+        diagnostico(self.codigo, self.mensagem, indice, fonte, parte)
+    }
+}
+
+impl From<anyhow::Error> for ErroLinha {
+    fn from(erro: anyhow::Error) -> Self {
+        Self::new(Parte::Operando, Codigo::OperandoInvalido, erro.to_string())
+    }
+}
+
+/// Erro de operação inválida. Em linhas recuadas o primeiro termo normalmente é uma
+/// operação escrita errado, e não um label, então ele é o termo indicado.
+fn operacao_invalida(linha: &Linha) -> ErroLinha {
+    let (parte, operacao) = match linha.label {
+        Some(label) if linha.recuada => (Parte::Label, label),
+        _ => (Parte::Operacao, linha.operacao),
+    };
+
+    ErroLinha::new(
+        parte,
+        Codigo::OperacaoInvalida,
+        format!("Operação inválida: {}", operacao),
+    )
+}
+
+/// Cria um diagnóstico sublinhando `parte`, que deve ser uma fatia de `fonte`.
+/// Partes vazias fora da linha, como um operando que não existe, ficam no final dela.
+fn diagnostico(
+    codigo: Codigo,
+    mensagem: String,
+    indice: usize,
+    fonte: &str,
+    parte: &str,
+) -> Diagnostico {
+    let inicio = (parte.as_ptr() as usize)
+        .checked_sub(fonte.as_ptr() as usize)
+        .filter(|inicio| inicio + parte.len() <= fonte.len())
+        .unwrap_or(fonte.trim_end().len());
+
+    let coluna = fonte[..inicio].chars().count();
+    let tamanho = parte.chars().count();
+    Diagnostico::new(
+        codigo,
+        mensagem,
+        indice + 1,
+        coluna..coluna + tamanho,
+        fonte,
+    )
+}
+
+/// Monta o programa com os dois passos, gerando o programa objeto e a listagem.
+///
+/// Cada passo reúne os diagnósticos de todas as linhas. O segundo passo só é feito
+/// quando o primeiro não encontra erros.
+pub fn montar(assembly: &str) -> Result<Montagem, ErroMontagem> {
+    let mut diagnosticos = Vec::new();
+    let secoes = separar_secoes(assembly, &mut diagnosticos);
+
+    let mut tabelas = Vec::with_capacity(secoes.len());
+    for secao in &secoes {
+        tabelas.push(primeiro_passo_secao(secao, &mut diagnosticos));
+    }
+
+    ErroMontagem::verificar(&diagnosticos)?;
+
+    let (objeto, linhas) = segundo_passo_secoes(&secoes, &tabelas, &mut diagnosticos);
+    ErroMontagem::verificar(&diagnosticos)?;
+
+    let nomes: Vec<&str> = secoes.iter().map(|secao| secao.nome).collect();
+    Ok(Montagem {
+        objeto,
+        listagem: listagem::gerar_listagem(assembly, &linhas, &nomes, &tabelas),
+        avisos: diagnosticos,
+        simbolos: simbolos_carregados(&secoes, &tabelas),
+    })
+}
+
+/// Labels de todas as seções com os endereços relativos ao início do programa carregado.
+/// Os símbolos absolutos, como os de EQU com números, não são endereços e ficam de fora.
+fn simbolos_carregados(secoes: &[Secao], tabelas: &[Tabelas]) -> Vec<(String, usize)> {
+    let mut simbolos = Vec::new();
+    let mut inicio_secao = 0;
+    for (secao, tabelas) in secoes.iter().zip(tabelas) {
+        for (nome, valor) in &tabelas.simbolos {
+            let endereco = usize::try_from(valor.valor)
+                .ok()
+                .and_then(|endereco| endereco.checked_sub(secao.endereco_inicial));
+
+            if valor.relativo
+                && let Some(endereco) = endereco
+            {
+                simbolos.push((nome.to_string(), inicio_secao + endereco));
+            }
+        }
+
+        let fim = tabelas
+            .blocos
+            .iter()
+            .map(|bloco| bloco.inicio + bloco.tamanho)
+            .max()
+            .unwrap_or(secao.endereco_inicial);
+
+        inicio_secao += fim - secao.endereco_inicial;
+    }
+
+    simbolos.sort();
+    simbolos
+}
+
+/// Estado do primeiro passo de uma seção de controle.
+struct PrimeiroPasso<'a> {
+    tabelas: Tabelas<'a>,
+    contador_localizacao: usize,
+    /// Literais usados desde o último LTORG, na ordem em que apareceram
+    literais_pendentes: Vec<&'a str>,
+    /// Contador salvo pelo último ORG com operando
+    contador_anterior: Option<usize>,
+    /// Nome e contador de localização de cada bloco
+    blocos: Vec<(&'a str, usize)>,
+    bloco_atual: usize,
+}
+
+pub(super) fn primeiro_passo_secao<'a>(
+    secao: &Secao<'a>,
+    diagnosticos: &mut Vec<Diagnostico>,
+) -> Tabelas<'a> {
+    // O bloco padrão começa no endereço do START
+    let mut passo = PrimeiroPasso {
+        tabelas: Tabelas::default(),
+        contador_localizacao: secao.endereco_inicial,
+        literais_pendentes: Vec::new(),
+        contador_anterior: None,
+        blocos: vec![("", secao.endereco_inicial)],
+        bloco_atual: 0,
+    };
+
+    for (indice, fonte) in &secao.linhas {
+        let Some(linha) = separar_linha(fonte) else {
+            if !remover_comentario(fonte).is_empty() {
+                let erro = ErroLinha::new(
+                    Parte::Linha,
+                    Codigo::LinhaSemOperacao,
+                    "Linha sem operação ignorada",
+                );
+
+                diagnosticos.push(erro.diagnostico(*indice, fonte, None));
+            }
 
-        let Some((label, linha)) = linha.trim().split_once(char::is_whitespace) else {
             continue;
         };
 
-        let operacao_linha;
-        let operando;
+        // Mesmo com o label inválido a linha ocupa o seu espaço, para não deslocar os
+        // endereços seguintes
+        if let Err(erro) = passo.definir_label(&linha) {
+            diagnosticos.push(erro.diagnostico(*indice, fonte, Some(&linha)));
+        }
 
-        if let Some(operacao) = TABELA_OPERACOES.get(label) {
-            operacao_linha = operacao;
-            operando = linha.trim();
-        } else {
-            if tabela_simbolos.contains_key(label) {
-                return Err(anyhow!("Símbolo {} definido múltiplas vezes", label));
+        match passo.processar_linha(&linha) {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(erro) => diagnosticos.push(erro.diagnostico(*indice, fonte, Some(&linha))),
+        }
+    }
+
+    match passo.finalizar(secao.endereco_inicial) {
+        Ok(tabelas) => tabelas,
+        Err(erro) => {
+            diagnosticos.push(erro.diagnostico(secao.indice_cabecalho, secao.cabecalho, None));
+            Tabelas::default()
+        }
+    }
+}
+
+impl<'a> PrimeiroPasso<'a> {
+    /// Define o label da linha com o contador de localização ou o valor do EQU.
+    fn definir_label(&mut self, linha: &Linha<'a>) -> Result<(), ErroLinha> {
+        let operacao_linha = tabela_operacoes::buscar_operacao(linha.operacao);
+        if let Some(label) = linha.label {
+            if self.tabelas.simbolos.contains_key(label) {
+                return Err(ErroLinha::new(
+                    Parte::Label,
+                    Codigo::SimboloDuplicado,
+                    format!("Símbolo {} definido múltiplas vezes", label),
+                ));
             }
 
-            tabela_simbolos.insert(label, contador_localizacao);
-            let Some((operacao, linha)) = linha.trim().split_once(char::is_whitespace) else {
-                continue;
-            };
+            if self.tabelas.referencias_externas.contains(&label) {
+                return Err(ErroLinha::new(
+                    Parte::Label,
+                    Codigo::ReferenciaExternaInvalida,
+                    format!("Símbolo {} definido localmente e no EXTREF", label),
+                ));
+            }
 
-            let Some(operacao) = TABELA_OPERACOES.get(operacao) else {
-                continue;
+            let valor = if let Some(Operacao::Equ) = operacao_linha {
+                expressoes::avaliar(
+                    linha.operando,
+                    &self.tabelas.simbolos,
+                    self.contador_localizacao,
+                )?
+            } else {
+                Valor::relativo(self.contador_localizacao as isize)
             };
 
-            operacao_linha = operacao;
-            operando = linha.trim();
+            self.tabelas.simbolos.insert(label, valor);
+        } else if let Some(Operacao::Equ) = operacao_linha {
+            return Err(ErroLinha::new(
+                Parte::Operacao,
+                Codigo::NomeInvalido,
+                "EQU sem label",
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Avança o contador de localização. Retorna true quando a linha é o END.
+    fn processar_linha(&mut self, linha: &Linha<'a>) -> Result<bool, ErroLinha> {
+        let Some(operacao_linha) = tabela_operacoes::buscar_operacao(linha.operacao) else {
+            return Err(operacao_invalida(linha));
+        };
+
+        if let Some(literal) = literal_operando(linha.operando)
+            && !self.tabelas.literais.contains_key(literal)
+            && !self.literais_pendentes.contains(&literal)
+        {
+            codigo_literal(literal)?;
+            self.literais_pendentes.push(literal);
         }
 
         match operacao_linha {
-            Operacao::Start => continue,
-            Operacao::End => break,
+            Operacao::End => return Ok(true),
+            Operacao::Ltorg => self.colocar_literais()?,
+            Operacao::Org => aplicar_org(
+                linha.operando,
+                &self.tabelas.simbolos,
+                &mut self.contador_localizacao,
+                &mut self.contador_anterior,
+            )?,
 
-            Operacao::Byte => {
-                if let Some(tipo) = operando.get(..1)
-                    && let Some(valor) = operando.get(1..)
+            Operacao::Use => {
+                self.blocos[self.bloco_atual].1 = self.contador_localizacao;
+                self.bloco_atual = if let Some(bloco) = self
+                    .blocos
+                    .iter()
+                    .position(|(nome, _)| *nome == linha.operando)
                 {
-                    let tamanho = valor.trim_matches('\'').len();
-                    if tipo == "C" {
-                        contador_localizacao += tamanho;
-                    } else if tipo == "X" {
-                        contador_localizacao += tamanho.div_ceil(2);
+                    bloco
+                } else {
+                    let bloco = self.blocos.len();
+                    self.blocos.push((linha.operando, bloco << BITS_BLOCO));
+                    bloco
+                };
+
+                self.contador_localizacao = self.blocos[self.bloco_atual].1;
+            }
+
+            Operacao::ExtDef => self
+                .tabelas
+                .definicoes_externas
+                .extend(separar_lista_simbolos(linha.operando)?),
+
+            Operacao::ExtRef => {
+                for simbolo in separar_lista_simbolos(linha.operando)? {
+                    if self.tabelas.simbolos.contains_key(simbolo) {
+                        return Err(ErroLinha::new(
+                            Parte::Operando,
+                            Codigo::ReferenciaExternaInvalida,
+                            format!("Símbolo {} definido localmente e no EXTREF", simbolo),
+                        ));
                     }
+
+                    self.tabelas.referencias_externas.push(simbolo);
                 }
             }
 
-            Operacao::Word => {
-                contador_localizacao += 3;
+            _ => {
+                self.contador_localizacao += tamanho_operacao(
+                    &operacao_linha,
+                    linha.operando,
+                    &self.tabelas.simbolos,
+                    self.contador_localizacao,
+                )?
             }
+        }
 
-            Operacao::ReserveWord => {
-                contador_localizacao += 3 * operando.parse::<usize>().unwrap_or_default();
-            }
+        if self.contador_localizacao & MASCARA_BLOCO > TAMANHO_MEMORIA_MAXIMO {
+            return Err(ErroLinha::new(
+                Parte::Operando,
+                Codigo::ForaDoAlcance,
+                "Contador de localização fora do espaço de endereçamento",
+            ));
+        }
 
-            Operacao::ReserveBytes => {
-                contador_localizacao += operando.parse::<usize>().unwrap_or_default();
-            }
+        Ok(false)
+    }
 
-            Operacao::Instrucao { hex: _, tamanho } => {
-                contador_localizacao += tamanho;
-            }
+    /// Coloca o pool de literais no endereço atual.
+    fn colocar_literais(&mut self) -> anyhow::Result<()> {
+        for literal in self.literais_pendentes.drain(..) {
+            self.tabelas
+                .literais
+                .insert(literal, self.contador_localizacao);
+            self.contador_localizacao += codigo_literal(literal)?.len() / 2;
         }
+
+        Ok(())
     }
 
-    Ok(tabela_simbolos)
-}
+    /// Coloca os literais restantes no final da seção e calcula o início de cada bloco,
+    /// relocando os símbolos e literais para os endereços finais.
+    fn finalizar(mut self, endereco_inicial: usize) -> Result<Tabelas<'a>, ErroLinha> {
+        self.colocar_literais()?;
+        self.blocos[self.bloco_atual].1 = self.contador_localizacao;
 
-pub fn segundo_passo(
-    assembly: &str,
-    tabela_simbolos: &HashMap<&str, usize>,
-) -> anyhow::Result<String> {
-    // Pular linhas no começo que são só comentários
-    let mut linhas = assembly.lines().skip_while(|l| l.trim().starts_with("."));
+        // Blocos são colocados um após o outro, na ordem em que foram definidos
+        let mut tabelas = self.tabelas;
+        let mut inicio_blocos = Vec::with_capacity(self.blocos.len());
+        let mut fim_anterior = 0;
+        for (indice, (nome, contador)) in self.blocos.into_iter().enumerate() {
+            let inicio = if indice == 0 {
+                endereco_inicial
+            } else {
+                fim_anterior
+            };
 
-    let mut nome_programa = "";
-    let mut endereco_inicial = 0;
+            let deslocamento = contador & MASCARA_BLOCO;
+            let fim = fim_anterior + deslocamento;
+            tabelas.blocos.push(Bloco {
+                nome,
+                inicio,
+                tamanho: fim - inicio,
+            });
+
+            inicio_blocos.push(fim_anterior);
+            fim_anterior = fim;
+        }
 
-    if let Some(linha) = linhas.next() {
-        let mut linha = linha.split_whitespace();
-        let Some(nome) = linha.next() else {
-            return Err(anyhow!("Programa não possui nome"));
+        let relocar = |endereco: usize| -> anyhow::Result<usize> {
+            let Some(inicio) = inicio_blocos.get(endereco >> BITS_BLOCO) else {
+                return Err(anyhow!("Expressão com termos de blocos diferentes"));
+            };
+
+            Ok(inicio + (endereco & MASCARA_BLOCO))
         };
 
-        if nome.len() > 6 {
-            return Err(anyhow!("Nome do programa tem tamanho maior que 6 bytes"));
+        for valor in tabelas.simbolos.values_mut() {
+            if valor.relativo {
+                valor.valor = relocar(valor.valor as usize)? as isize;
+            }
         }
 
-        nome_programa = nome;
-
-        if let Some(operador) = linha.next()
-            && operador == "START"
-            && let Some(operando) = linha.next()
-        {
-            let operando = usize::from_str_radix(operando, 16).unwrap_or_default();
-            endereco_inicial = operando;
+        for endereco in tabelas.literais.values_mut() {
+            *endereco = relocar(*endereco)?;
         }
+
+        Ok(tabelas)
+    }
+}
+
+fn segundo_passo_secoes<'a>(
+    secoes: &[Secao<'a>],
+    tabelas: &[Tabelas],
+    diagnosticos: &mut Vec<Diagnostico>,
+) -> (String, Vec<LinhaListagem<'a>>) {
+    let mut objetos = Vec::with_capacity(secoes.len());
+    let mut linhas = Vec::new();
+    for (indice, (secao, tabelas)) in secoes.iter().zip(tabelas).enumerate() {
+        objetos.push(segundo_passo_secao(
+            secao,
+            tabelas,
+            indice == 0,
+            &mut linhas,
+            diagnosticos,
+        ));
     }
 
-    let mut codigo_objeto = String::from("");
-    for linha in linhas {
-        // Remover comentários
-        let linha = linha
-            .split_once(".")
-            .map(|(linha, _)| linha)
-            .unwrap_or(linha);
+    (objetos.join("\n"), linhas)
+}
+
+/// Estado do segundo passo de uma seção de controle.
+struct SegundoPasso<'a, 't> {
+    nome_programa: &'a str,
+    tabelas: &'t Tabelas<'t>,
+    contador_localizacao: usize,
+    /// Conteúdo assumido do registrador B, usado no endereçamento relativo à base
+    base: Option<usize>,
+    contador_anterior: Option<usize>,
+    fim_programa: usize,
+    /// Contador de localização de cada bloco, já com o endereço de início do bloco
+    contadores_blocos: Vec<usize>,
+    bloco_atual: usize,
+    literais_pendentes: Vec<&'a str>,
+    literais_colocados: Vec<&'a str>,
+    /// Trechos contínuos de código objeto e seus endereços
+    segmentos: Vec<(usize, String)>,
+    /// Registros de modificação, já formatados
+    modificacoes: Vec<String>,
+}
+
+/// Gera o programa objeto de uma seção, adicionando o endereço e o código objeto
+/// de cada linha à listagem.
+fn segundo_passo_secao<'a>(
+    secao: &Secao<'a>,
+    tabelas: &Tabelas,
+    principal: bool,
+    listagem: &mut Vec<LinhaListagem<'a>>,
+    diagnosticos: &mut Vec<Diagnostico>,
+) -> String {
+    let mut passo = SegundoPasso {
+        nome_programa: secao.nome,
+        tabelas,
+        contador_localizacao: secao.endereco_inicial,
+        base: None,
+        contador_anterior: None,
+        fim_programa: secao.endereco_inicial,
+        contadores_blocos: tabelas.blocos.iter().map(|b| b.inicio).collect(),
+        bloco_atual: 0,
+        literais_pendentes: Vec::new(),
+        literais_colocados: Vec::new(),
+        segmentos: Vec::new(),
+        modificacoes: Vec::new(),
+    };
+
+    listagem.push(LinhaListagem::fonte(
+        secao.indice_cabecalho,
+        Some(secao.endereco_inicial),
+    ));
 
-        let Some((label, linha)) = linha.trim().split_once(char::is_whitespace) else {
+    // Literais que sobrarem são listados depois da última linha da seção
+    let mut ultimo_indice = secao.indice_cabecalho;
+
+    for (indice, fonte) in &secao.linhas {
+        let Some(linha) = separar_linha(fonte) else {
             continue;
         };
 
-        let operacao_linha;
-        let mut operando;
+        ultimo_indice = *indice;
+        match passo.processar_linha(*indice, &linha, listagem) {
+            Ok(true) => break,
+            Ok(false) => {}
+            Err(erro) => diagnosticos.push(erro.diagnostico(*indice, fonte, Some(&linha))),
+        }
+    }
 
-        if let Some(operacao) = TABELA_OPERACOES.get(label) {
-            operacao_linha = operacao;
-            operando = linha.trim();
-        } else {
-            let Some((operacao, linha)) = linha.trim().split_once(char::is_whitespace) else {
-                continue;
-            };
+    match passo.finalizar(secao.endereco_inicial, principal, ultimo_indice, listagem) {
+        Ok(objeto) => objeto,
+        Err(erro) => {
+            diagnosticos.push(erro.diagnostico(secao.indice_cabecalho, secao.cabecalho, None));
+            String::new()
+        }
+    }
+}
 
-            let Some(operacao) = TABELA_OPERACOES.get(operacao) else {
-                return Err(anyhow!("Operação inválida: {}", operacao));
-            };
+impl<'a> SegundoPasso<'a, '_> {
+    /// Gera o código objeto de uma linha e a adiciona à listagem.
+    /// Retorna true quando a linha é o END.
+    fn processar_linha(
+        &mut self,
+        indice: usize,
+        linha: &Linha<'a>,
+        listagem: &mut Vec<LinhaListagem<'a>>,
+    ) -> Result<bool, ErroLinha> {
+        let tabelas = self.tabelas;
+        let Some(operacao_linha) = tabela_operacoes::buscar_operacao(linha.operacao) else {
+            return Err(operacao_invalida(linha));
+        };
 
-            operacao_linha = operacao;
-            operando = linha.trim();
+        if let Operacao::End = operacao_linha {
+            listagem.push(LinhaListagem::fonte(indice, None));
+            return Ok(true);
         }
 
+        if let Some(literal) = literal_operando(linha.operando)
+            && !self.literais_colocados.contains(&literal)
+            && !self.literais_pendentes.contains(&literal)
+        {
+            self.literais_pendentes.push(literal);
+        }
+
+        let operando = linha.operando;
+        let endereco_linha = self.contador_localizacao;
+        self.contador_localizacao +=
+            tamanho_operacao(&operacao_linha, operando, &tabelas.simbolos, endereco_linha)?;
+
+        let mut codigo_linha = String::new();
+        // Literais colocados por esta linha, listados logo abaixo dela
+        let mut literais_linha = Vec::new();
         match operacao_linha {
-            Operacao::End => break,
-            Operacao::Byte => {
-                if let Some(tipo) = operando.get(..1)
-                    && let Some(valor) = operando.get(1..)
-                {
-                    let valor = valor.trim_matches('\'');
-                    if tipo == "C" {
-                        for c in valor.chars() {
-                            if !c.is_ascii() {
-                                return Err(anyhow!("Caractere não ASCII: {}", c));
-                            }
-
-                            codigo_objeto.push_str(format!("{:02X}", c as u8).as_str());
-                        }
-                    } else if tipo == "X" {
-                        let Ok(valor) = u8::from_str_radix(valor, 16) else {
-                            return Err(anyhow!("Byte inválido: {}", valor));
-                        };
-
-                        codigo_objeto.push_str(format!("{:02X}", valor).as_str());
-                    }
+            Operacao::Ltorg => {
+                for literal in self.literais_pendentes.drain(..) {
+                    let codigo = codigo_literal(literal)?;
+                    literais_linha.push((literal, self.contador_localizacao, codigo.clone()));
+                    self.contador_localizacao += codigo.len() / 2;
+                    codigo_linha.push_str(codigo.as_str());
+                    self.literais_colocados.push(literal);
                 }
             }
 
-            Operacao::Word => {
-                let Ok(word) = operando.parse::<u32>() else {
-                    return Err(anyhow!("WORD inválida: {}", operando));
+            Operacao::Org => aplicar_org(
+                operando,
+                &tabelas.simbolos,
+                &mut self.contador_localizacao,
+                &mut self.contador_anterior,
+            )?,
+
+            Operacao::Use => {
+                let Some(bloco) = tabelas.blocos.iter().position(|b| b.nome == operando) else {
+                    return Err(anyhow!("Bloco não encontrado: {}", operando).into());
                 };
 
-                // Limite de 24 bits
-                if word > 0xFFFFFF {
-                    return Err(anyhow!("WORD inválida: {}", operando));
+                if let Some(contador) = self.contadores_blocos.get_mut(self.bloco_atual) {
+                    *contador = self.contador_localizacao;
                 }
 
-                codigo_objeto.push_str(format!("{:06X}", word).as_str());
+                self.bloco_atual = bloco;
+                self.contador_localizacao = self.contadores_blocos[bloco];
             }
 
-            Operacao::Instrucao { hex, tamanho } => {
-                if *tamanho == 2 {
-                    codigo_objeto.push_str(format!("{:02X}", hex).as_str());
-                    match *hex {
-                        opcodes::CLEAR | opcodes::TIXR => {
-                            let r1 = if let Some(r1) = TABELA_REGISTRADORES.get(operando) {
-                                *r1
-                            } else {
-                                let Ok(r1) = operando.parse::<u8>() else {
-                                    return Err(anyhow!("Registrador 1 inválido: {}", operando));
-                                };
-
-                                if r1 > 9 {
-                                    return Err(anyhow!("Registrador 1 inválido"));
-                                }
-
-                                r1
-                            };
-
-                            codigo_objeto.push_str(format!("{:X}0", r1).as_str());
-                        }
-
-                        _ => {
-                            let Some((r1, r2)) = operando.split_once(',') else {
-                                return Err(anyhow!("Operando inválido, esperado r1,r2"));
-                            };
-
-                            let r1 = r1.trim();
-                            let r2 = r2.trim();
-
-                            let r1 = if let Some(r1) = TABELA_REGISTRADORES.get(r1) {
-                                *r1
-                            } else {
-                                let r1 = r1
-                                    .parse::<u8>()
-                                    .context(format!("Registrador 1 inválido: {}", r1))?;
-
-                                if r1 > 9 {
-                                    return Err(anyhow!("Registrador 1 inválido"));
-                                }
-
-                                r1
-                            };
-
-                            let r2 = if let Some(r2) = TABELA_REGISTRADORES.get(r2) {
-                                *r2
-                            } else {
-                                let r2 = r2
-                                    .parse::<u8>()
-                                    .context(format!("Registrador 2 inválido: {}", r2))?;
-
-                                if r2 > 9 {
-                                    return Err(anyhow!("Registrador 1 inválido"));
-                                }
-
-                                r2
-                            };
-
-                            codigo_objeto.push_str(format!("{:X}{:X}", r1, r2).as_str());
-                        }
-                    }
-                } else {
-                    // Modos de endereçamento (Formatos 3 e 4)
-                    let enderecamento: u8 = if operando.starts_with("#") {
-                        operando = operando.trim_start_matches("#");
-                        1
-                    } else if operando.starts_with("@") {
-                        operando = operando.trim_start_matches("@");
-                        2
-                    } else {
-                        3
-                    };
-
-                    codigo_objeto.push_str(format!("{:02X}", hex | enderecamento).as_str());
-
-                    let mut flags_restantes = 0;
-                    if *tamanho == 4 {
-                        flags_restantes |= 1; // Flag e (extended)
-                    }
+            Operacao::Base => {
+                let endereco = expressoes::avaliar(operando, &tabelas.simbolos, endereco_linha)?;
+                self.base = Some(endereco.valor as usize);
+            }
 
-                    if operando.ends_with(",X") {
-                        operando = operando.trim_end_matches(",X").trim(); // Trim aqui também!
-                        flags_restantes |= 8; // Flag x (indexado)
-                    }
+            Operacao::NoBase => self.base = None,
+            Operacao::Byte => codigo_linha.push_str(codigo_byte(operando)?.as_str()),
 
-                    let operando = if operando.is_empty() {
-                        0
-                    } else if let Some(local) = tabela_simbolos.get(operando) {
-                        *local
-                    } else {
-                        operando.parse::<usize>().context(format!(
-                            "Símbolo não encontrado ou número inválido: '{}'",
-                            operando
-                        ))?
-                    };
-
-                    // Verifica tamanho e capacidade
-                    if operando > 4095 && *tamanho < 4 {
-                        return Err(anyhow!("Valor muito grande para formato 3: {}", operando));
+            Operacao::Word => {
+                let (word, referencias) = expressoes::avaliar_com_externos(
+                    operando,
+                    &tabelas.simbolos,
+                    &tabelas.referencias_externas,
+                    endereco_linha,
+                )?;
+
+                // Limite de 24 bits, valores negativos em complemento de 2
+                if !(-0x800000..=0xFFFFFF).contains(&word.valor) {
+                    return Err(ErroLinha::new(
+                        Parte::Operando,
+                        Codigo::ForaDoAlcance,
+                        format!("WORD inválida: {}", operando),
+                    ));
+                }
+
+                codigo_linha.push_str(format!("{:06X}", word.valor & 0xFFFFFF).as_str());
+                adicionar_modificacoes(
+                    &mut self.modificacoes,
+                    endereco_linha,
+                    6,
+                    word.relativo.then_some(self.nome_programa),
+                    &referencias,
+                );
+            }
+
+            Operacao::ExtDef => {
+                for simbolo in separar_lista_simbolos(operando)? {
+                    if !tabelas.simbolos.contains_key(simbolo) {
+                        return Err(ErroLinha::new(
+                            Parte::Operando,
+                            Codigo::ReferenciaExternaInvalida,
+                            format!("Símbolo externo não definido: {}", simbolo),
+                        ));
                     }
+                }
+            }
 
-                    codigo_objeto.push_str(format!("{:X}", flags_restantes).as_str());
-                    if *tamanho < 4 {
-                        codigo_objeto.push_str(format!("{:03X}", operando).as_str());
-                    } else {
-                        codigo_objeto.push_str(format!("{:05X}", operando).as_str());
+            Operacao::Instrucao { definicao, tamanho } => {
+                if definicao.operandos == Operandos::Nenhum && !operando.is_empty() {
+                    return Err(
+                        anyhow!("Instrução {} não possui operando", definicao.mnemonico).into(),
+                    );
+                }
+
+                match definicao.formato {
+                    // Formato 1 é somente o opcode
+                    Formato::Um => {
+                        codigo_linha.push_str(format!("{:02X}", definicao.opcode).as_str())
+                    }
+                    Formato::Dois => {
+                        codigo_linha.push_str(codigo_formato_2(definicao, operando)?.as_str())
                     }
+                    _ => codigo_linha.push_str(
+                        self.codigo_formato_3_4(
+                            definicao.opcode,
+                            tamanho,
+                            operando,
+                            endereco_linha,
+                        )?
+                        .as_str(),
+                    ),
                 }
             }
 
-            _ => continue,
+            _ => {}
+        }
+
+        // EQU mostra o valor do símbolo no lugar do endereço
+        let endereco_listado = match (operacao_linha, linha.label) {
+            (Operacao::Equ, Some(label)) => tabelas
+                .simbolos
+                .get(label)
+                .map(|valor| valor.valor as usize & 0xFFFFFF),
+            _ => Some(endereco_linha),
+        };
+
+        if literais_linha.is_empty() {
+            listagem.push(LinhaListagem {
+                codigo: codigo_linha.clone(),
+                ..LinhaListagem::fonte(indice, endereco_listado)
+            });
+        } else {
+            listagem.push(LinhaListagem::fonte(indice, endereco_listado));
+        }
+
+        for (literal, endereco, codigo) in literais_linha {
+            listagem.push(LinhaListagem::literal(indice, literal, endereco, codigo));
         }
+
+        self.fim_programa = self.fim_programa.max(self.contador_localizacao);
+        adicionar_segmento(&mut self.segmentos, endereco_linha, codigo_linha);
+        Ok(false)
     }
 
-    let mut objeto_final = format!(
-        "H{nome_programa} {:06X}{:06X}\n",
-        endereco_inicial,
-        codigo_objeto.len() / 2
-    );
+    /// Código objeto de uma instrução de formato 3 ou 4.
+    fn codigo_formato_3_4(
+        &mut self,
+        hex: u8,
+        tamanho: usize,
+        operando: &str,
+        endereco_linha: usize,
+    ) -> Result<String, ErroLinha> {
+        let tabelas = self.tabelas;
+        let mut operando = operando;
+        let mut codigo = String::new();
+
+        // Modos de endereçamento (Formatos 3 e 4)
+        let enderecamento: u8 = if operando.starts_with("#") {
+            operando = operando.trim_start_matches("#");
+            1
+        } else if operando.starts_with("@") {
+            operando = operando.trim_start_matches("@");
+            2
+        } else {
+            3
+        };
+
+        codigo.push_str(format!("{:02X}", hex | enderecamento).as_str());
+
+        let mut flags_restantes = 0;
+        if tamanho == 4 {
+            flags_restantes |= 1; // Flag e (extended)
+        }
+
+        if operando.ends_with(",X") {
+            operando = operando.trim_end_matches(",X").trim(); // Trim aqui também!
+            flags_restantes |= 8; // Flag x (indexado)
+        }
 
-    let mut cursor = 0;
-    let mut endereco_registro = endereco_inicial;
+        let (valor, referencias) = if operando.is_empty() {
+            (Valor::absoluto(0), Vec::new())
+        } else if let Some(local) = tabelas.literais.get(operando) {
+            (Valor::relativo(*local as isize), Vec::new())
+        } else {
+            expressoes::avaliar_com_externos(
+                operando,
+                &tabelas.simbolos,
+                &tabelas.referencias_externas,
+                endereco_linha,
+            )?
+        };
+
+        if let Some(referencia) = referencias.first()
+            && tamanho < 4
+        {
+            return Err(ErroLinha::new(
+                Parte::Operando,
+                Codigo::ReferenciaExternaInvalida,
+                format!(
+                    "Referência externa {} só pode ser usada no formato 4",
+                    referencia.simbolo
+                ),
+            ));
+        }
 
-    while cursor < codigo_objeto.len() {
-        // Pega no máximo 510 chars (255 bytes) por vez
-        let chunk_size = std::cmp::min(510, codigo_objeto.len() - cursor);
-        let chunk = codigo_objeto
-            .get(cursor..(cursor + chunk_size))
-            .unwrap_or_default();
+        // O endereço do formato 4 começa no meio do segundo byte da instrução
+        if tamanho == 4 {
+            adicionar_modificacoes(
+                &mut self.modificacoes,
+                endereco_linha + 1,
+                5,
+                valor.relativo.then_some(self.nome_programa),
+                &referencias,
+            );
+        }
+
+        // Endereços relativos no formato 3 são endereçados relativos ao PC ou à base.
+        // O PC já aponta para a próxima instrução durante a execução.
+        let operando = if valor.relativo && tamanho < 4 {
+            let (flags, deslocamento) =
+                calcular_deslocamento(valor.valor as usize, self.contador_localizacao, self.base)
+                    .map_err(|erro| {
+                    ErroLinha::new(Parte::Operando, Codigo::ForaDoAlcance, erro.to_string())
+                })?;
+
+            flags_restantes |= flags;
+            deslocamento
+        } else {
+            let Ok(valor) = usize::try_from(valor.valor) else {
+                return Err(anyhow!("Valor negativo inválido: {}", valor.valor).into());
+            };
 
-        objeto_final.push_str(
-            format!("T{:06X}{:02X}{chunk}\n", endereco_registro, chunk.len() / 2).as_str(),
+            valor
+        };
+
+        // Verifica tamanho e capacidade
+        if operando > 4095 && tamanho < 4 {
+            return Err(ErroLinha::new(
+                Parte::Operando,
+                Codigo::ForaDoAlcance,
+                format!("Valor muito grande para formato 3: {}", operando),
+            ));
+        }
+
+        if operando > 0xFFFFF {
+            return Err(ErroLinha::new(
+                Parte::Operando,
+                Codigo::ForaDoAlcance,
+                format!("Valor muito grande para formato 4: {}", operando),
+            ));
+        }
+
+        codigo.push_str(format!("{:X}", flags_restantes).as_str());
+        if tamanho < 4 {
+            codigo.push_str(format!("{:03X}", operando).as_str());
+        } else {
+            codigo.push_str(format!("{:05X}", operando).as_str());
+        }
+
+        Ok(codigo)
+    }
+
+    /// Coloca os literais restantes no final da seção e gera os registros do programa objeto.
+    fn finalizar(
+        self,
+        endereco_inicial: usize,
+        principal: bool,
+        ultimo_indice: usize,
+        listagem: &mut Vec<LinhaListagem<'a>>,
+    ) -> Result<String, ErroLinha> {
+        let nome_programa = self.nome_programa;
+        let tabelas = self.tabelas;
+        let mut segmentos = self.segmentos;
+
+        // Literais restantes ficam no final da seção
+        let mut codigo_literais = String::new();
+        for literal in self.literais_pendentes {
+            let codigo = codigo_literal(literal)?;
+            let endereco = self.contador_localizacao + codigo_literais.len() / 2;
+            codigo_literais.push_str(codigo.as_str());
+            listagem.push(LinhaListagem::literal(
+                ultimo_indice,
+                literal,
+                endereco,
+                codigo,
+            ));
+        }
+
+        let fim_programa = self
+            .fim_programa
+            .max(self.contador_localizacao + codigo_literais.len() / 2);
+        adicionar_segmento(&mut segmentos, self.contador_localizacao, codigo_literais);
+
+        let mut objeto_final = format!(
+            "H{nome_programa} {:06X}{:06X}\n",
+            endereco_inicial,
+            fim_programa - endereco_inicial
         );
 
-        endereco_registro += chunk.len() / 2;
-        cursor += chunk_size;
+        if !tabelas.definicoes_externas.is_empty() {
+            objeto_final.push('D');
+            for simbolo in &tabelas.definicoes_externas {
+                // Os símbolos não definidos já foram reportados na linha do EXTDEF
+                let Some(valor) = tabelas.simbolos.get(simbolo) else {
+                    continue;
+                };
+
+                objeto_final.push_str(format!("{:<6}{:06X}", simbolo, valor.valor).as_str());
+            }
+
+            objeto_final.push('\n');
+        }
+
+        if !tabelas.referencias_externas.is_empty() {
+            objeto_final.push('R');
+            for simbolo in &tabelas.referencias_externas {
+                objeto_final.push_str(format!("{:<6}", simbolo).as_str());
+            }
+
+            objeto_final.push('\n');
+        }
+
+        for (endereco_segmento, codigo_objeto) in segmentos {
+            let mut cursor = 0;
+            let mut endereco_registro = endereco_segmento;
+
+            while cursor < codigo_objeto.len() {
+                // Pega no máximo 510 chars (255 bytes) por vez
+                let chunk_size = std::cmp::min(510, codigo_objeto.len() - cursor);
+                let chunk = codigo_objeto
+                    .get(cursor..(cursor + chunk_size))
+                    .unwrap_or_default();
+
+                objeto_final.push_str(
+                    format!("T{:06X}{:02X}{chunk}\n", endereco_registro, chunk.len() / 2).as_str(),
+                );
+
+                endereco_registro += chunk.len() / 2;
+                cursor += chunk_size;
+            }
+        }
+
+        for modificacao in self.modificacoes {
+            objeto_final.push_str(modificacao.as_str());
+            objeto_final.push('\n');
+        }
+
+        // Somente a seção principal indica onde começa a execução
+        if principal {
+            objeto_final.push_str(format!("E{:06X}", endereco_inicial).as_str());
+        } else {
+            objeto_final.push('E');
+        }
+
+        Ok(objeto_final)
+    }
+}
+
+/// Código objeto de uma instrução de formato 2, com os operandos da definição dela.
+fn codigo_formato_2(definicao: &DefinicaoInstrucao, operando: &str) -> anyhow::Result<String> {
+    let (r1, r2) = match definicao.operandos {
+        Operandos::Registrador => (numero_registrador(operando)?, 0),
+        Operandos::Numero => match operando.parse::<u8>() {
+            Ok(n) if n <= 15 => (n, 0),
+            _ => {
+                return Err(anyhow!(
+                    "Número da chamada ao supervisor inválido: {}",
+                    operando
+                ));
+            }
+        },
+
+        _ => {
+            let Some((r1, r2)) = operando.split_once(',') else {
+                return Err(anyhow!("Operando inválido, esperado r1,r2"));
+            };
+
+            let r1 = numero_registrador(r1.trim())?;
+            let r2 = r2.trim();
+
+            // Os deslocamentos guardam o número de bits menos 1
+            if definicao.operandos == Operandos::RegistradorNumero {
+                match r2.parse::<u8>() {
+                    Ok(n) if (1..=16).contains(&n) => (r1, n - 1),
+                    _ => return Err(anyhow!("Número de bits inválido: {}", r2)),
+                }
+            } else {
+                (r1, numero_registrador(r2)?)
+            }
+        }
+    };
+
+    Ok(format!("{:02X}{:X}{:X}", definicao.opcode, r1, r2))
+}
+
+/// Número de um registrador, pelo nome ou pelo próprio número.
+fn numero_registrador(registrador: &str) -> anyhow::Result<u8> {
+    if let Some(numero) = TABELA_REGISTRADORES.get(registrador) {
+        return Ok(*numero);
+    }
+
+    match registrador.parse::<u8>() {
+        Ok(numero) if numero <= 9 => Ok(numero),
+        _ => Err(anyhow!("Registrador inválido: {}", registrador)),
+    }
+}
+
+/// Separa as seções de controle do programa. A primeira linha que não é comentário
+/// inicia a seção principal e cada CSECT inicia uma nova seção.
+pub(super) fn separar_secoes<'a>(
+    assembly: &'a str,
+    diagnosticos: &mut Vec<Diagnostico>,
+) -> Vec<Secao<'a>> {
+    // Pular linhas no começo que são só comentários
+    let mut linhas = assembly
+        .lines()
+        .enumerate()
+        .skip_while(|(_, l)| l.trim().starts_with("."));
+
+    let mut nome_programa = "";
+    let mut endereco_inicial = 0;
+    let mut indice_cabecalho = 0;
+    let mut cabecalho = "";
+
+    if let Some((indice, linha)) = linhas.next() {
+        indice_cabecalho = indice;
+        cabecalho = linha;
+
+        let mut partes = linha.split_whitespace();
+        if let Some(nome) = partes.next() {
+            if nome.len() > 6 {
+                diagnosticos.push(diagnostico(
+                    Codigo::NomeInvalido,
+                    "Nome do programa tem tamanho maior que 6 bytes".to_string(),
+                    indice,
+                    linha,
+                    nome,
+                ));
+            }
+
+            nome_programa = nome;
+        } else {
+            diagnosticos.push(diagnostico(
+                Codigo::NomeInvalido,
+                "Programa não possui nome".to_string(),
+                indice,
+                linha,
+                "",
+            ));
+        }
+
+        if let Some(operador) = partes.next()
+            && operador == "START"
+            && let Some(operando) = partes.next()
+        {
+            match usize::from_str_radix(operando, 16) {
+                Ok(operando) => endereco_inicial = operando,
+                Err(_) => diagnosticos.push(diagnostico(
+                    Codigo::OperandoInvalido,
+                    format!("Endereço inicial inválido: {}", operando),
+                    indice,
+                    linha,
+                    operando,
+                )),
+            }
+        }
+    }
+
+    let mut secoes = vec![Secao {
+        nome: nome_programa,
+        endereco_inicial,
+        indice_cabecalho,
+        cabecalho,
+        linhas: Vec::new(),
+    }];
+
+    for (indice, linha) in linhas {
+        if let Some(linha_separada) = separar_linha(linha)
+            && let Some(Operacao::Csect) =
+                tabela_operacoes::buscar_operacao(linha_separada.operacao)
+        {
+            let nome = match linha_separada.label {
+                Some(nome) if nome.len() > 6 => {
+                    let erro = ErroLinha::new(
+                        Parte::Label,
+                        Codigo::NomeInvalido,
+                        "Nome da seção tem tamanho maior que 6 bytes",
+                    );
+
+                    diagnosticos.push(erro.diagnostico(indice, linha, Some(&linha_separada)));
+                    nome
+                }
+
+                Some(nome) => nome,
+                None => {
+                    let erro =
+                        ErroLinha::new(Parte::Operacao, Codigo::NomeInvalido, "CSECT sem nome");
+                    diagnosticos.push(erro.diagnostico(indice, linha, Some(&linha_separada)));
+                    ""
+                }
+            };
+
+            // Seções de controle sempre começam no endereço 0
+            secoes.push(Secao {
+                nome,
+                endereco_inicial: 0,
+                indice_cabecalho: indice,
+                cabecalho: linha,
+                linhas: Vec::new(),
+            });
+        } else if let Some(secao) = secoes.last_mut() {
+            secao.linhas.push((indice, linha));
+        }
+    }
+
+    secoes
+}
+
+/// Linha de código assembly separada em suas partes.
+struct Linha<'a> {
+    label: Option<&'a str>,
+    operacao: &'a str,
+    operando: &'a str,
+    /// A linha começa com espaços
+    recuada: bool,
+}
+
+/// Remove o comentário de uma linha, junto com os espaços das pontas.
+fn remover_comentario(linha: &str) -> &str {
+    linha
+        .split_once(".")
+        .map(|(linha, _)| linha)
+        .unwrap_or(linha)
+        .trim()
+}
+
+/// Remove o comentário de uma linha e separa label, operação e operando.
+/// Retorna None para linhas vazias ou que não possuem operação.
+fn separar_linha(linha: &str) -> Option<Linha<'_>> {
+    let recuada = linha.starts_with(char::is_whitespace);
+    let linha = remover_comentario(linha);
+    let (primeiro, resto) = linha.split_once(char::is_whitespace).unwrap_or((linha, ""));
+
+    if primeiro.is_empty() {
+        return None;
+    }
+
+    if tabela_operacoes::buscar_operacao(primeiro).is_some() {
+        return Some(Linha {
+            label: None,
+            operacao: primeiro,
+            operando: resto.trim(),
+            recuada,
+        });
+    }
+
+    let resto = resto.trim();
+    let (operacao, operando) = resto.split_once(char::is_whitespace).unwrap_or((resto, ""));
+
+    if operacao.is_empty() {
+        return None;
+    }
+
+    Some(Linha {
+        label: Some(primeiro),
+        operacao,
+        operando: operando.trim(),
+        recuada,
+    })
+}
+
+/// Separa a lista de símbolos do EXTDEF e do EXTREF.
+fn separar_lista_simbolos(operando: &str) -> anyhow::Result<Vec<&str>> {
+    let mut simbolos = Vec::new();
+    for simbolo in operando.split(',').map(str::trim) {
+        // Registros D e R guardam nomes de até 6 caracteres
+        if simbolo.is_empty() || simbolo.len() > 6 {
+            return Err(anyhow!("Símbolo externo inválido: '{}'", simbolo));
+        }
+
+        simbolos.push(simbolo);
+    }
+
+    Ok(simbolos)
+}
+
+/// Adiciona o código de uma linha ao último trecho de código objeto, ou começa
+/// um novo trecho quando o endereço não for contínuo.
+fn adicionar_segmento(segmentos: &mut Vec<(usize, String)>, endereco: usize, codigo: String) {
+    if codigo.is_empty() {
+        return;
+    }
+
+    match segmentos.last_mut() {
+        Some((inicio, anterior)) if *inicio + anterior.len() / 2 == endereco => {
+            anterior.push_str(codigo.as_str())
+        }
+
+        _ => segmentos.push((endereco, codigo)),
+    }
+}
+
+/// Adiciona os registros de modificação de um campo, cujo tamanho é dado em meio bytes.
+/// Campos relativos ao início da seção são somados ao endereço da própria seção, e
+/// cada referência externa é somada ou subtraída do campo.
+fn adicionar_modificacoes(
+    modificacoes: &mut Vec<String>,
+    endereco: usize,
+    tamanho: usize,
+    secao_relativa: Option<&str>,
+    referencias: &[ReferenciaExterna],
+) {
+    if let Some(secao) = secao_relativa {
+        modificacoes.push(format!("M{:06X}{:02X}+{secao}", endereco, tamanho));
+    }
+
+    for referencia in referencias {
+        let sinal = if referencia.negativa { '-' } else { '+' };
+        modificacoes.push(format!(
+            "M{:06X}{:02X}{sinal}{}",
+            endereco, tamanho, referencia.simbolo
+        ));
+    }
+}
+
+/// Código objeto em hexadecimal de uma constante no formato C'..' ou X'..'.
+fn codigo_byte(operando: &str) -> anyhow::Result<String> {
+    let mut codigo = String::new();
+    if let Some(tipo) = operando.get(..1)
+        && let Some(valor) = operando.get(1..)
+    {
+        let valor = valor.trim_matches('\'');
+        if tipo == "C" {
+            for c in valor.chars() {
+                if !c.is_ascii() {
+                    return Err(anyhow!("Caractere não ASCII: {}", c));
+                }
+
+                codigo.push_str(format!("{:02X}", c as u8).as_str());
+            }
+        } else if tipo == "X" {
+            if !valor.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(anyhow!("Byte inválido: {}", valor));
+            }
+
+            // Completar com zero à esquerda quando o número de dígitos for ímpar
+            if valor.len() % 2 != 0 {
+                codigo.push('0');
+            }
+
+            codigo.push_str(valor.to_ascii_uppercase().as_str());
+        }
+    }
+
+    Ok(codigo)
+}
+
+/// Retorna o literal usado por um operando, caso exista.
+fn literal_operando(operando: &str) -> Option<&str> {
+    let operando = operando.trim_end_matches(",X").trim();
+    operando.starts_with('=').then_some(operando)
+}
+
+/// Código objeto em hexadecimal de um literal. Literais C'..' e X'..' seguem
+/// as regras do BYTE, enquanto valores numéricos ocupam uma palavra.
+fn codigo_literal(literal: &str) -> anyhow::Result<String> {
+    let valor = literal.trim_start_matches('=');
+    if valor.starts_with("C'") || valor.starts_with("X'") {
+        return codigo_byte(valor);
+    }
+
+    let Ok(word) = valor.parse::<u32>() else {
+        return Err(anyhow!("Literal inválido: {}", literal));
+    };
+
+    // Limite de 24 bits
+    if word > 0xFFFFFF {
+        return Err(anyhow!("Literal inválido: {}", literal));
+    }
+
+    Ok(format!("{:06X}", word))
+}
+
+/// Aplica a diretiva ORG. Com operando, salva o contador atual e o substitui pelo
+/// valor da expressão. Sem operando, restaura o contador salvo pelo último ORG.
+fn aplicar_org(
+    operando: &str,
+    simbolos: &HashMap<&str, Valor>,
+    contador_localizacao: &mut usize,
+    contador_anterior: &mut Option<usize>,
+) -> anyhow::Result<()> {
+    if operando.is_empty() {
+        *contador_localizacao = contador_anterior
+            .take()
+            .context("ORG sem operando sem um ORG anterior")?;
+
+        return Ok(());
+    }
+
+    let valor = expressoes::avaliar(operando, simbolos, *contador_localizacao)?;
+    let Ok(endereco) = usize::try_from(valor.valor) else {
+        return Err(anyhow!("Endereço inválido para ORG: {}", operando));
+    };
+
+    *contador_anterior = Some(*contador_localizacao);
+    *contador_localizacao = endereco;
+    Ok(())
+}
+
+/// Quantidade de bytes que uma operação ocupa no programa objeto.
+fn tamanho_operacao(
+    operacao: &Operacao,
+    operando: &str,
+    simbolos: &HashMap<&str, Valor>,
+    contador_localizacao: usize,
+) -> anyhow::Result<usize> {
+    let tamanho = match operacao {
+        Operacao::Byte => {
+            if let Some(tipo) = operando.get(..1)
+                && let Some(valor) = operando.get(1..)
+            {
+                let tamanho = valor.trim_matches('\'').len();
+                if tipo == "C" {
+                    return Ok(tamanho);
+                } else if tipo == "X" {
+                    return Ok(tamanho.div_ceil(2));
+                }
+            }
+
+            0
+        }
+
+        Operacao::Word => 3,
+        Operacao::ReserveWord => {
+            3 * quantidade_reservada(operando, simbolos, contador_localizacao)?
+        }
+
+        Operacao::ReserveBytes => quantidade_reservada(operando, simbolos, contador_localizacao)?,
+        Operacao::Instrucao { tamanho, .. } => *tamanho,
+        _ => 0,
+    };
+
+    Ok(tamanho)
+}
+
+/// Avalia o operando de RESB ou RESW, que deve ser um valor absoluto e positivo.
+fn quantidade_reservada(
+    operando: &str,
+    simbolos: &HashMap<&str, Valor>,
+    contador_localizacao: usize,
+) -> anyhow::Result<usize> {
+    let quantidade = expressoes::avaliar(operando, simbolos, contador_localizacao)?;
+    if quantidade.relativo || quantidade.valor < 0 {
+        return Err(anyhow!(
+            "Quantidade de memória reservada inválida: {}",
+            operando
+        ));
+    }
+
+    if quantidade.valor as usize > TAMANHO_MEMORIA_MAXIMO {
+        return Err(anyhow!(
+            "Quantidade de memória reservada maior que o espaço de endereçamento: {}",
+            operando
+        ));
+    }
+
+    Ok(quantidade.valor as usize)
+}
+
+/// Calcula o deslocamento de uma instrução de formato 3, tentando primeiro o
+/// endereçamento relativo ao PC (-2048..2047) e depois relativo à base (0..4095).
+/// O endereçamento direto não é usado, o endereço mudaria ao carregar o programa em
+/// outro lugar sem um registro M.
+///
+/// Retorna as flags b/p a serem setadas e o deslocamento de 12 bits.
+fn calcular_deslocamento(
+    endereco_alvo: usize,
+    pc: usize,
+    base: Option<usize>,
+) -> anyhow::Result<(u8, usize)> {
+    let relativo_pc = endereco_alvo as isize - pc as isize;
+    if (-2048..=2047).contains(&relativo_pc) {
+        // Complemento de 2 em 12 bits
+        return Ok((2, (relativo_pc as usize) & 0xFFF));
+    }
+
+    if let Some(base) = base
+        && let Some(relativo_base) = endereco_alvo.checked_sub(base)
+        && relativo_base <= 4095
+    {
+        return Ok((4, relativo_base));
     }
 
-    objeto_final.push_str(format!("E{:06X}", endereco_inicial).as_str());
-    Ok(objeto_final)
+    Err(anyhow!(
+        "Endereço {:06X} fora do alcance do formato 3, use o formato 4 (+) ou BASE",
+        endereco_alvo
+    ))
 }
@@ -1,6 +1,8 @@
-use crate::maquina::constantes::opcodes;
+use crate::maquina::conjunto_instrucoes::{self, DefinicaoInstrucao};
+use crate::maquina::instrucao::Formato;
 use phf::phf_map;
 
+#[derive(Clone, Copy)]
 pub enum Operacao {
     Start,
     End,
@@ -8,344 +10,63 @@ pub enum Operacao {
     Word,
     ReserveWord,
     ReserveBytes,
-    Instrucao { hex: u8, tamanho: usize },
+    Base,
+    NoBase,
+    Ltorg,
+    Equ,
+    Org,
+    Use,
+    Csect,
+    ExtDef,
+    ExtRef,
+    Instrucao {
+        definicao: &'static DefinicaoInstrucao,
+        tamanho: usize,
+    },
 }
 
-pub static TABELA_OPERACOES: phf::Map<&'static str, Operacao> = phf_map! {
+/// Diretivas do montador. As instruções vêm do conjunto de instruções da máquina.
+static TABELA_OPERACOES: phf::Map<&'static str, Operacao> = phf_map! {
     "START" => Operacao::Start,
     "END" => Operacao::End,
     "BYTE" => Operacao::Byte,
     "WORD" => Operacao::Word,
     "RESW" => Operacao::ReserveWord,
     "RESB" => Operacao::ReserveBytes,
+    "BASE" => Operacao::Base,
+    "NOBASE" => Operacao::NoBase,
+    "LTORG" => Operacao::Ltorg,
+    "EQU" => Operacao::Equ,
+    "ORG" => Operacao::Org,
+    "USE" => Operacao::Use,
+    "CSECT" => Operacao::Csect,
+    "EXTDEF" => Operacao::ExtDef,
+    "EXTREF" => Operacao::ExtRef,
+};
 
-    "ADD" => Operacao::Instrucao {
-        hex: opcodes::ADD,
-        tamanho: 3,
-    },
-
-    "CLEAR" => Operacao::Instrucao {
-        hex: opcodes::CLEAR,
-        tamanho: 2,
-    },
-
-    "STA" => Operacao::Instrucao {
-        hex: opcodes::STA,
-        tamanho: 3,
-    },
-
-    "+ADD" => Operacao::Instrucao {
-        hex: opcodes::ADD,
-        tamanho: 4,
-    },
-
-    "ADDR" => Operacao::Instrucao {
-        hex: opcodes::ADDR,
-        tamanho: 2,
-    },
-
-    "AND" => Operacao::Instrucao {
-        hex: opcodes::AND,
-        tamanho: 3,
-    },
-
-    "+AND" => Operacao::Instrucao {
-        hex: opcodes::AND,
-        tamanho: 4,
-    },
-
-    "COMP" => Operacao::Instrucao {
-        hex: opcodes::COMP,
-        tamanho: 3,
-    },
-
-    "+COMP" => Operacao::Instrucao {
-        hex: opcodes::COMP,
-        tamanho: 4,
-    },
-
-    "COMPR" => Operacao::Instrucao {
-        hex: opcodes::COMPR,
-        tamanho: 2,
-    },
-
-    "DIV" => Operacao::Instrucao {
-        hex: opcodes::DIV,
-        tamanho: 3,
-    },
-
-    "+DIV" => Operacao::Instrucao {
-        hex: opcodes::DIV,
-        tamanho: 4,
-    },
-
-    "DIVR" => Operacao::Instrucao {
-        hex: opcodes::DIVR,
-        tamanho: 2,
-    },
-
-    "J" => Operacao::Instrucao {
-        hex: opcodes::J,
-        tamanho: 3,
-    },
-
-    "+J" => Operacao::Instrucao {
-        hex: opcodes::J,
-        tamanho: 4,
-    },
-
-    "JEQ" => Operacao::Instrucao {
-        hex: opcodes::JEQ,
-        tamanho: 3,
-    },
-
-    "+JEQ" => Operacao::Instrucao {
-        hex: opcodes::JEQ,
-        tamanho: 4,
-    },
-
-    "JGT" => Operacao::Instrucao {
-        hex: opcodes::JGT,
-        tamanho: 3,
-    },
-
-    "+JGT" => Operacao::Instrucao {
-        hex: opcodes::JGT,
-        tamanho: 4,
-    },
-
-    "JLT" => Operacao::Instrucao {
-        hex: opcodes::JLT,
-        tamanho: 3,
-    },
-
-    "+JLT" => Operacao::Instrucao {
-        hex: opcodes::JLT,
-        tamanho: 4,
-    },
-
-    "JSUB" => Operacao::Instrucao {
-        hex: opcodes::JSUB,
-        tamanho: 3,
-    },
-
-    "+JSUB" => Operacao::Instrucao {
-        hex: opcodes::JSUB,
-        tamanho: 4,
-    },
-
-    "LDA" => Operacao::Instrucao {
-        hex: opcodes::LDA,
-        tamanho: 3,
-    },
-
-    "+LDA" => Operacao::Instrucao {
-        hex: opcodes::LDA,
-        tamanho: 4,
-    },
-
-    "LDB" => Operacao::Instrucao {
-        hex: opcodes::LDB,
-        tamanho: 3,
-    },
-
-    "+LDB" => Operacao::Instrucao {
-        hex: opcodes::LDB,
-        tamanho: 4,
-    },
-
-    "LDCH" => Operacao::Instrucao {
-        hex: opcodes::LDCH,
-        tamanho: 3,
-    },
-
-    "+LDCH" => Operacao::Instrucao {
-        hex: opcodes::LDCH,
-        tamanho: 4,
-    },
-
-    "LDL" => Operacao::Instrucao {
-        hex: opcodes::LDL,
-        tamanho: 3,
-    },
-
-    "+LDL" => Operacao::Instrucao {
-        hex: opcodes::LDL,
-        tamanho: 4,
-    },
-
-    "LDS" => Operacao::Instrucao {
-        hex: opcodes::LDS,
-        tamanho: 3,
-    },
-
-    "+LDS" => Operacao::Instrucao {
-        hex: opcodes::LDS,
-        tamanho: 4,
-    },
-
-    "LDT" => Operacao::Instrucao {
-        hex: opcodes::LDT,
-        tamanho: 3,
-    },
-
-    "+LDT" => Operacao::Instrucao {
-        hex: opcodes::LDT,
-        tamanho: 4,
-    },
-
-    "LDX" => Operacao::Instrucao {
-        hex: opcodes::LDX,
-        tamanho: 3,
-    },
-
-    "+LDX" => Operacao::Instrucao {
-        hex: opcodes::LDX,
-        tamanho: 4,
-    },
-
-    "MUL" => Operacao::Instrucao {
-        hex: opcodes::MUL,
-        tamanho: 3,
-    },
-
-    "+MUL" => Operacao::Instrucao {
-        hex: opcodes::MUL,
-        tamanho: 4,
-    },
-
-    "MULR" => Operacao::Instrucao {
-        hex: opcodes::MULR,
-        tamanho: 2,
-    },
-
-    "OR" => Operacao::Instrucao {
-        hex: opcodes::OR,
-        tamanho: 3,
-    },
-
-    "+OR" => Operacao::Instrucao {
-        hex: opcodes::OR,
-        tamanho: 4,
-    },
-
-    "RMO" => Operacao::Instrucao {
-        hex: opcodes::RMO,
-        tamanho: 2,
-    },
-
-    "RSUB" => Operacao::Instrucao {
-        hex: opcodes::RSUB,
-        tamanho: 3,
-    },
-
-    "+RSUB" => Operacao::Instrucao {
-        hex: opcodes::RSUB,
-        tamanho: 4,
-    },
-
-    "SHIFTL" => Operacao::Instrucao {
-        hex: opcodes::SHIFTL,
-        tamanho: 2,
-    },
-
-    "SHIFTR" => Operacao::Instrucao {
-        hex: opcodes::SHIFTR,
-        tamanho: 2,
-    },
-
-    "+STA" => Operacao::Instrucao {
-        hex: opcodes::STA,
-        tamanho: 4,
-    },
-
-    "STB" => Operacao::Instrucao {
-        hex: opcodes::STB,
-        tamanho: 3,
-    },
-
-    "+STB" => Operacao::Instrucao {
-        hex: opcodes::STB,
-        tamanho: 4,
-    },
-
-    "STCH" => Operacao::Instrucao {
-        hex: opcodes::STCH,
-        tamanho: 3,
-    },
-
-    "+STCH" => Operacao::Instrucao {
-        hex: opcodes::STCH,
-        tamanho: 4,
-    },
-
-    "STL" => Operacao::Instrucao {
-        hex: opcodes::STL,
-        tamanho: 3,
-    },
-
-    "+STL" => Operacao::Instrucao {
-        hex: opcodes::STL,
-        tamanho: 4,
-    },
-
-    "STS" => Operacao::Instrucao {
-        hex: opcodes::STS,
-        tamanho: 3,
-    },
-
-    "+STS" => Operacao::Instrucao {
-        hex: opcodes::STS,
-        tamanho: 4,
-    },
-
-    "STT" => Operacao::Instrucao {
-        hex: opcodes::STT,
-        tamanho: 3,
-    },
-
-    "+STT" => Operacao::Instrucao {
-        hex: opcodes::STT,
-        tamanho: 4,
-    },
-
-    "STX" => Operacao::Instrucao {
-        hex: opcodes::STX,
-        tamanho: 3,
-    },
-
-    "+STX" => Operacao::Instrucao {
-        hex: opcodes::STX,
-        tamanho: 4,
-    },
-
-    "SUB" => Operacao::Instrucao {
-        hex: opcodes::SUB,
-        tamanho: 3,
-    },
-
-    "+SUB" => Operacao::Instrucao {
-        hex: opcodes::SUB,
-        tamanho: 4,
-    },
+/// Busca uma diretiva ou instrução pelo nome. O + antes do mnemônico indica o formato 4,
+/// que só existe para as instruções de formato 3.
+pub fn buscar_operacao(nome: &str) -> Option<Operacao> {
+    if let Some(diretiva) = TABELA_OPERACOES.get(nome) {
+        return Some(*diretiva);
+    }
 
-    "SUBR" => Operacao::Instrucao {
-        hex: opcodes::SUBR,
-        tamanho: 2,
-    },
+    let (mnemonico, estendida) = match nome.strip_prefix('+') {
+        Some(mnemonico) => (mnemonico, true),
+        None => (nome, false),
+    };
 
-    "TIX" => Operacao::Instrucao {
-        hex: opcodes::TIX,
-        tamanho: 3,
-    },
+    let definicao = conjunto_instrucoes::por_mnemonico(mnemonico)?;
+    if estendida && !definicao.estendida() {
+        return None;
+    }
 
-    "+TIX" => Operacao::Instrucao {
-        hex: opcodes::TIX,
-        tamanho: 4,
-    },
+    let tamanho = match definicao.formato {
+        _ if estendida => 4,
+        Formato::Um => 1,
+        Formato::Dois => 2,
+        _ => 3,
+    };
 
-    "TIXR" => Operacao::Instrucao {
-        hex: opcodes::TIXR,
-        tamanho: 2,
-    }
-};
+    Some(Operacao::Instrucao { definicao, tamanho })
+}
@@ -1,36 +1,428 @@
-use crate::montador::montador::{primeiro_passo, segundo_passo};
+use crate::montador::diagnostico::{Codigo, ErroMontagem, Severidade};
+use crate::montador::expressoes::{Valor, avaliar};
+use crate::montador::montador::{self, Bloco, Tabelas, montar};
 use std::collections::HashMap;
 
+/// Tabelas do primeiro passo de cada seção de controle, sem montar o programa.
+fn primeiro_passo(assembly: &str) -> Result<Vec<Tabelas<'_>>, ErroMontagem> {
+    let mut diagnosticos = Vec::new();
+    let secoes = montador::separar_secoes(assembly, &mut diagnosticos);
+
+    let mut tabelas = Vec::with_capacity(secoes.len());
+    for secao in &secoes {
+        tabelas.push(montador::primeiro_passo_secao(secao, &mut diagnosticos));
+    }
+
+    ErroMontagem::verificar(&diagnosticos)?;
+    Ok(tabelas)
+}
+
 #[test]
 fn primeiro_passo_add() {
     let add = include_str!("../../programas_exemplo/add.asm");
     let mut simbolos = HashMap::with_capacity(2);
-    simbolos.insert("INICIO", 0x1000);
-    simbolos.insert("STORE", 0x1006);
+    simbolos.insert("INICIO", Valor::relativo(0x1000));
+    simbolos.insert("STORE", Valor::relativo(0x1006));
 
-    assert_eq!(primeiro_passo(add).unwrap(), simbolos);
+    assert_eq!(primeiro_passo(add).unwrap()[0].simbolos, simbolos);
 }
 
 #[test]
 fn primeiro_passo_byte() {
     let byte = include_str!("../../programas_exemplo/byte.asm");
     let mut simbolos = HashMap::with_capacity(2);
-    simbolos.insert("INICIO", 0x1000);
-    simbolos.insert("ADD_1", 0x1007);
+    simbolos.insert("INICIO", Valor::relativo(0x1000));
+    simbolos.insert("ADD_1", Valor::relativo(0x1007));
 
-    assert_eq!(primeiro_passo(byte).unwrap(), simbolos);
+    assert_eq!(primeiro_passo(byte).unwrap()[0].simbolos, simbolos);
 }
 
 #[test]
 fn montar_add() {
     let add = include_str!("../../programas_exemplo/add.asm");
     let mut simbolos = HashMap::with_capacity(2);
-    simbolos.insert("INICIO", 0x1000);
-    simbolos.insert("STORE", 0x1006);
+    simbolos.insert("INICIO", Valor::relativo(0x1000));
+    simbolos.insert("STORE", Valor::relativo(0x1006));
 
-    assert_eq!(primeiro_passo(add).unwrap(), simbolos);
+    let tabelas = primeiro_passo(add).unwrap();
+    assert_eq!(tabelas[0].simbolos, simbolos);
     assert_eq!(
-        segundo_passo(add, &simbolos).unwrap(),
+        montar(add).unwrap().objeto,
         "HT_ADD 00100000000B\nT0010000B1900011900010D0000B400\nE001000"
     );
 }
+
+#[test]
+fn montar_relativo_pc() {
+    let relativo = include_str!("../../programas_exemplo/relativo.asm");
+    assert_eq!(
+        montar(relativo).unwrap().objeto,
+        "HT_REL 001000000012\nT0010000F0320091B20060F20063F2FF4000005\nE001000"
+    );
+}
+
+#[test]
+fn montar_fora_do_alcance() {
+    let distante = include_str!("../../programas_exemplo/distante.asm");
+    let erro = montar(distante).unwrap_err();
+    assert_eq!(erro.diagnosticos.len(), 1);
+    assert_eq!(erro.diagnosticos[0].codigo, Codigo::ForaDoAlcance);
+    assert_eq!(erro.diagnosticos[0].linha, 4);
+    assert_eq!(erro.diagnosticos[0].colunas, 16..20);
+
+    // Mesmo cabendo em 12 bits, um endereço relocável não pode ser direto
+    let programa = "PROG START 0\n LDA DADO\nBUFFER RESB 3000\nDADO WORD 7\n END";
+    let erro = montar(programa).unwrap_err();
+    assert_eq!(erro.diagnosticos[0].codigo, Codigo::ForaDoAlcance);
+
+    // Reservas maiores que o espaço de endereçamento de 20 bits
+    let programa = "PROG START 0\nA RESW 4000000000000000000\n END";
+    let erro = montar(programa).unwrap_err();
+    assert_eq!(erro.diagnosticos[0].codigo, Codigo::OperandoInvalido);
+
+    let programa = "PROG START 0\nA RESB 600000\nB RESB 600000\n END";
+    let erro = montar(programa).unwrap_err();
+    assert_eq!(erro.diagnosticos[0].codigo, Codigo::ForaDoAlcance);
+    assert_eq!(erro.diagnosticos[0].linha, 3);
+}
+
+#[test]
+fn montar_relativo_base() {
+    let base = include_str!("../../programas_exemplo/base.asm");
+
+    assert_eq!(
+        montar(base).unwrap().objeto,
+        "HT_BASE 000000001010\nT0000000A6910100A034000034003\nT00100A06000007000008\nM00000105+T_BASE\nE000000"
+    );
+}
+
+#[test]
+fn montar_literais() {
+    let literais = include_str!("../../programas_exemplo/literais.asm");
+    let tabelas = primeiro_passo(literais).unwrap();
+
+    let mut enderecos = HashMap::with_capacity(3);
+    enderecos.insert("=X'000005'", 0x06);
+    enderecos.insert("=C'EOF'", 0x09);
+    enderecos.insert("=3", 0x12);
+    assert_eq!(tabelas[0].literais, enderecos);
+
+    assert_eq!(
+        montar(literais).unwrap().objeto,
+        "HT_LIT 000000000015\nT000000150320032B2003000005454F461B20031B2000000003\nE000000"
+    );
+}
+
+#[test]
+fn montar_equ() {
+    let equ = include_str!("../../programas_exemplo/equ.asm");
+    let mut simbolos = HashMap::with_capacity(7);
+    simbolos.insert("MAXLEN", Valor::absoluto(4096));
+    simbolos.insert("INICIO", Valor::relativo(0x1000));
+    simbolos.insert("FIM", Valor::relativo(0x100A));
+    simbolos.insert("BUFFER", Valor::relativo(0x100D));
+    simbolos.insert("BUFEND", Valor::relativo(0x1017));
+    simbolos.insert("TAMANHO", Valor::absoluto(10));
+    simbolos.insert("TABELA", Valor::relativo(0x1017));
+
+    let tabelas = primeiro_passo(equ).unwrap();
+    assert_eq!(tabelas[0].simbolos, simbolos);
+    assert_eq!(
+        montar(equ).unwrap().objeto,
+        "HT_EQU 00100000001D\nT0010000D7510100001000A07201000000B\nE001000"
+    );
+}
+
+#[test]
+fn expressoes_relativas() {
+    let mut simbolos = HashMap::with_capacity(2);
+    simbolos.insert("INICIO", Valor::relativo(0x1000));
+    simbolos.insert("FIM", Valor::relativo(0x1010));
+
+    assert_eq!(
+        avaliar("FIM-INICIO", &simbolos, 0).unwrap(),
+        Valor::absoluto(0x10)
+    );
+
+    assert_eq!(
+        avaliar("INICIO+2*3", &simbolos, 0).unwrap(),
+        Valor::relativo(0x1006)
+    );
+
+    assert_eq!(
+        avaliar("*-2", &simbolos, 0x1004).unwrap(),
+        Valor::relativo(0x1002)
+    );
+    assert!(avaliar("FIM+INICIO", &simbolos, 0).is_err());
+    assert!(avaliar("INICIO*2", &simbolos, 0).is_err());
+
+    // Um único termo relativo negativo não é um problema de blocos
+    for expressao in ["-INICIO", "5-INICIO"] {
+        let erro = avaliar(expressao, &simbolos, 0).unwrap_err().to_string();
+        assert!(erro.contains("não pareados"), "{}", erro);
+    }
+    assert!(avaliar("99999999999*99999999999", &simbolos, 0).is_err());
+    assert!(avaliar("9000000000000000000+9000000000000000000", &simbolos, 0).is_err());
+    assert!(avaliar("-9000000000000000000-9000000000000000000", &simbolos, 0).is_err());
+}
+
+#[test]
+fn montar_org() {
+    let org = include_str!("../../programas_exemplo/org.asm");
+    let tabelas = primeiro_passo(org).unwrap();
+
+    assert_eq!(
+        tabelas[0].simbolos.get("VALOR"),
+        Some(&Valor::relativo(0x10))
+    );
+    assert_eq!(
+        montar(org).unwrap().objeto,
+        "HT_ORG 000000000013\nT0000000303200D\nT00001003000003\nT000003033F2FFA\nE000000"
+    );
+}
+
+#[test]
+fn montar_blocos() {
+    let blocos = include_str!("../../programas_exemplo/blocos.asm");
+    let mut simbolos = HashMap::with_capacity(6);
+    simbolos.insert("INICIO", Valor::relativo(0x00));
+    simbolos.insert("TAMANHO", Valor::relativo(0x0A));
+    simbolos.insert("BUFFER", Valor::relativo(0x10));
+    simbolos.insert("BUFEND", Valor::relativo(0x1010));
+    simbolos.insert("MAXLEN", Valor::absoluto(4096));
+    simbolos.insert("FIM", Valor::relativo(0x0D));
+
+    let tabelas = primeiro_passo(blocos).unwrap();
+    assert_eq!(tabelas[0].simbolos, simbolos);
+    assert_eq!(
+        tabelas[0].blocos,
+        vec![
+            Bloco {
+                nome: "",
+                inicio: 0x00,
+                tamanho: 0x0A
+            },
+            Bloco {
+                nome: "CDADOS",
+                inicio: 0x0A,
+                tamanho: 0x06
+            },
+            Bloco {
+                nome: "CBLKS",
+                inicio: 0x10,
+                tamanho: 0x1000
+            }
+        ]
+    );
+
+    assert_eq!(
+        montar(blocos).unwrap().objeto,
+        "HT_USE 000000001010\nT00000003032007\nT00000A03000005\nT00000307751010003F2FF6\nT00000D03000000\nE000000"
+    );
+
+    // A distância entre blocos diferentes só é conhecida no segundo passo
+    let distancia = "PROG START 0\nX1 WORD 1\n USE DADOS\nY1 WORD 2\n USE\n LDA #Y1-X1\n END";
+    assert_eq!(
+        montar(distancia).unwrap().objeto,
+        "HPROG 000000000009\nT00000003000001\nT00000603000002\nT00000303010006\nE000000"
+    );
+
+    let equ = "PROG START 0\nX1 WORD 1\n USE DADOS\nY1 WORD 2\nD EQU Y1-X1\n END";
+    let erro = montar(equ).unwrap_err();
+    assert_eq!(erro.diagnosticos[0].codigo, Codigo::OperandoInvalido);
+    assert_eq!(erro.diagnosticos[0].linha, 5);
+}
+
+#[test]
+fn montar_secoes() {
+    let secoes = include_str!("../../programas_exemplo/secoes.asm");
+    let tabelas = primeiro_passo(secoes).unwrap();
+
+    assert_eq!(tabelas.len(), 2);
+    assert_eq!(tabelas[0].definicoes_externas, vec!["LISTA", "ENDA"]);
+    assert_eq!(tabelas[0].referencias_externas, vec!["LISTB", "ROTB"]);
+    assert_eq!(
+        tabelas[1].simbolos.get("LISTB"),
+        Some(&Valor::relativo(0x07))
+    );
+
+    assert_eq!(
+        montar(secoes).unwrap().objeto,
+        "HPROGA 00000000000D\n\
+         DLISTA 000007ENDA  00000A\n\
+         RLISTB ROTB  \n\
+         T0000000D4B100000032000000004FFFFFC\n\
+         M00000105+ROTB\n\
+         M00000A06+LISTB\n\
+         E000000\n\
+         HPROGB 00000000000A\n\
+         DLISTB 000007ROTB  000000\n\
+         RLISTA ENDA  \n\
+         T0000000A031000004F0000000000\n\
+         M00000105+LISTA\n\
+         M00000706+ENDA\n\
+         M00000706-LISTA\n\
+         E"
+    );
+
+    // Os labels da PROGB ficam depois dos 13 bytes da PROGA
+    let simbolos = montar(secoes).unwrap().simbolos;
+    assert!(simbolos.contains(&("LISTA".to_string(), 0x07)));
+    assert!(simbolos.contains(&("ROTB".to_string(), 0x0D)));
+    assert!(simbolos.contains(&("LISTB".to_string(), 0x14)));
+
+    // Um símbolo do EXTREF não pode ser definido na própria seção, antes ou depois
+    for programa in [
+        "PROG START 0\n EXTREF X\nX WORD 1\n END",
+        "PROG START 0\nX WORD 1\n EXTREF X\n END",
+    ] {
+        let diagnosticos = montar(programa).unwrap_err().diagnosticos;
+        assert_eq!(diagnosticos.len(), 1);
+        assert_eq!(diagnosticos[0].codigo, Codigo::ReferenciaExternaInvalida);
+        assert_eq!(diagnosticos[0].linha, 3);
+    }
+}
+
+#[test]
+fn montar_modificacoes() {
+    let modificacao = include_str!("../../programas_exemplo/modificacao.asm");
+
+    assert_eq!(
+        montar(modificacao).unwrap().objeto,
+        "HT_MOD 000000000014\n\
+         T000000144B1000073F20094F000000000700001001100005\n\
+         M00000105+T_MOD\n\
+         M00000A06+T_MOD\n\
+         E000000"
+    );
+}
+
+#[test]
+fn referencia_externa_formato_3() {
+    let programa = "PROG START 0\n EXTREF ROTB\n JSUB ROTB\n END";
+    assert!(montar(programa).is_err());
+}
+
+#[test]
+fn montar_listagem() {
+    let literais = include_str!("../../programas_exemplo/literais.asm");
+    let montagem = montar(literais).unwrap();
+
+    let linhas: Vec<&str> = montagem.listagem.lines().collect();
+    assert_eq!(
+        linhas[..14],
+        [
+            "Linha  Ender   Código fonte                             Código objeto",
+            "    1          . Programa teste do pool de literais",
+            "    2  000000  T_LIT   START 0",
+            "    3",
+            "    4  000000  INICIO      LDA =X'000005'               032003",
+            "    5  000003              COMP =C'EOF'                 2B2003",
+            "    6  000006              LTORG",
+            "       000006  *       =X'000005'                       000005",
+            "       000009  *       =C'EOF'                          454F46",
+            "    7  00000C              ADD =3                       1B2003",
+            "    8  00000F              ADD =3 . Literal repetido usa o mesmo endereço 1B2000",
+            "    9",
+            "   10                      END INICIO",
+            "       000012  *       =3                               000003",
+        ]
+    );
+
+    assert_eq!(
+        linhas[14..],
+        [
+            "",
+            "Tabela de símbolos (T_LIT)",
+            "Símbolo   Valor   Tipo",
+            "INICIO    000000  R",
+            "",
+            "Tabela de literais (T_LIT)",
+            "Literal         Endereço",
+            "=3              000012",
+            "=C'EOF'         000009",
+            "=X'000005'      000006",
+        ]
+    );
+}
+
+#[test]
+fn montar_diagnosticos() {
+    let erros = include_str!("../../programas_exemplo/erros.asm");
+    let diagnosticos = montar(erros).unwrap_err().diagnosticos;
+
+    // Todos os problemas do primeiro passo são reunidos
+    let resumo: Vec<_> = diagnosticos
+        .iter()
+        .map(|d| (d.severidade, d.codigo, d.linha, d.colunas.clone()))
+        .collect();
+
+    assert_eq!(
+        resumo,
+        [
+            (Severidade::Erro, Codigo::OperacaoInvalida, 4, 12..15),
+            (Severidade::Erro, Codigo::SimboloDuplicado, 5, 0..6),
+            (Severidade::Aviso, Codigo::LinhaSemOperacao, 6, 0..5),
+        ]
+    );
+
+    assert_eq!(
+        diagnosticos[0].to_string(),
+        "erro[E001]: Operação inválida: LDZ\n \
+         --> linha 4, coluna 13\n  \
+         |\n\
+         4 |             LDZ VALOR\n  \
+         |             ^^^"
+    );
+
+    // A linha com o label duplicado ainda ocupa espaço no contador de localização
+    let programa = "PROG START 0\nX RESB 1\nX RESB 1048575\nY RESB 1\n END";
+    let resumo: Vec<_> = montar(programa)
+        .unwrap_err()
+        .diagnosticos
+        .iter()
+        .map(|d| (d.codigo, d.linha))
+        .collect();
+
+    assert_eq!(
+        resumo,
+        [(Codigo::SimboloDuplicado, 3), (Codigo::ForaDoAlcance, 4)]
+    );
+
+    // O EXTDEF de um símbolo não definido é reportado uma vez, na linha dele
+    let programa = "PROG START 0\n EXTDEF Y\n RSUB\n END";
+    let diagnosticos = montar(programa).unwrap_err().diagnosticos;
+    assert_eq!(diagnosticos.len(), 1);
+    assert_eq!(diagnosticos[0].codigo, Codigo::ReferenciaExternaInvalida);
+    assert_eq!(diagnosticos[0].linha, 2);
+}
+
+#[test]
+fn montar_ponto_flutuante() {
+    let programa = "PROG START 0\n FLOAT\n NORM\n FIX\n+STF VALOR\nVALOR RESW 2\n END";
+    assert_eq!(
+        montar(programa).unwrap().objeto,
+        "HPROG 00000000000D\nT00000007C0C8C483100007\nM00000405+PROG\nE000000"
+    );
+
+    let programa = "PROG START 0\n FIX A\n END";
+    assert!(montar(programa).is_err());
+}
+
+#[test]
+fn montar_instrucoes_formato_2() {
+    let programa = "PROG START 0\n RMO A,S\n SHIFTL T,4\n SHIFTR A,1\n SVC 3\n MULR X,A\n END";
+    assert_eq!(
+        montar(programa).unwrap().objeto,
+        "HPROG 00000000000A\nT0000000AAC04A453A800B0309810\nE000000"
+    );
+
+    // Formato 4 numa instrução de formato 2, bits demais no SHIFTL e operando no RSUB
+    for programa in [
+        "PROG START 0\n+RMO A,S\n END",
+        "PROG START 0\n SHIFTL A,17\n END",
+        "PROG START 0\n RSUB ROTINA\n END",
+    ] {
+        assert!(montar(programa).is_err());
+    }
+}